@@ -0,0 +1,456 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use camino::Utf8PathBuf;
+use clap::Args;
+use colored::Colorize;
+use serde::Serialize;
+
+use near_verify_rs::logic::attestation::{self, Statement};
+use near_verify_rs::logic::batch::{Checkouts, GitCheckouts};
+use near_verify_rs::logic::docker_checks::{self, ImageProvider};
+use near_verify_rs::logic::events::SharedObserver;
+use near_verify_rs::logic::nep330_build::{self, BuildOptions, CaptureOptions, Limits, OutputMode};
+use near_verify_rs::types::contract_source_metadata::ContractSourceMetadata;
+use near_verify_rs::types::policy::{Policy, TrustedKey};
+use near_verify_rs::types::report::{
+    BuildReport, DaemonReport, ImageReport, PlatformReport, ReportFormat, VerificationReport,
+    WhitelistDecision,
+};
+use near_verify_rs::types::sha256_checksum::SHA256Checksum;
+use near_verify_rs::types::source_id::SourceId;
+use near_verify_rs::types::whitelist::{self, Whitelist};
+
+/// Result of a command, printed as [Output::json] with `--json` and as [Output::human] otherwise
+pub struct Output {
+    pub success: bool,
+    pub json: serde_json::Value,
+    pub human: String,
+}
+
+#[derive(Debug, Args)]
+pub struct MetadataArgs {
+    /// Contract source metadata JSON file, as returned by `contract_source_metadata` view method
+    metadata: Utf8PathBuf,
+    /// Policy file (JSON or TOML) or legacy JSON list of allowed images and command prefixes
+    #[arg(long)]
+    whitelist: Option<Utf8PathBuf>,
+    /// Require `--whitelist` to have a detached signature (`.minisig` or `.sig`) of this key,
+    /// either `ed25519:<base64 or hex>` or minisign public key; can be repeated
+    #[arg(long = "trusted-key", requires = "whitelist")]
+    trusted_keys: Vec<String>,
+}
+
+impl MetadataArgs {
+    fn load(&self) -> eyre::Result<(ContractSourceMetadata, Option<Whitelist>)> {
+        let metadata = std::fs::read(&self.metadata)
+            .map_err(|err| eyre::eyre!("couldn't read `{}`: {}", self.metadata, err))?;
+        let metadata: ContractSourceMetadata = serde_json::from_slice(&metadata)
+            .map_err(|err| eyre::eyre!("malformed metadata `{}`: {}", self.metadata, err))?;
+        let whitelist = self
+            .whitelist
+            .as_ref()
+            .map(|path| -> eyre::Result<Whitelist> {
+                let whitelist = if self.trusted_keys.is_empty() {
+                    load_whitelist(path)?
+                } else {
+                    let trusted_keys = self
+                        .trusted_keys
+                        .iter()
+                        .map(|key| TrustedKey::parse(key))
+                        .collect::<eyre::Result<Vec<_>>>()?;
+                    Policy::load_signed(path, &trusted_keys)?.whitelist
+                };
+                let warnings = whitelist::lint(&whitelist).into_result().map_err(|err| {
+                    eyre::eyre!(
+                        "{}\ncheck whitelist with `near-verify-rs whitelist lint {}`",
+                        err,
+                        path
+                    )
+                })?;
+                for warning in warnings {
+                    eprintln!("{} {}", "Whitelist warning:".yellow(), warning);
+                }
+                Ok(whitelist)
+            })
+            .transpose()?;
+        Ok((metadata, whitelist))
+    }
+}
+
+fn load_whitelist(path: &camino::Utf8Path) -> eyre::Result<Whitelist> {
+    Ok(Policy::load(path)?.whitelist)
+}
+
+pub fn lint_whitelist(path: &camino::Utf8Path) -> eyre::Result<Output> {
+    let report = whitelist::lint(&load_whitelist(path)?);
+    let mut human = vec![];
+    for error in report.errors.iter() {
+        human.push(format!("{} {}", "error:".red(), error));
+    }
+    for warning in report.warnings.iter() {
+        human.push(format!("{} {}", "warning:".yellow(), warning));
+    }
+    if report.errors.is_empty() {
+        human.push(format!("{} is valid", path).green().to_string());
+    }
+    Ok(Output {
+        success: report.errors.is_empty(),
+        json: serde_json::to_value(&report)?,
+        human: human.join("\n"),
+    })
+}
+
+#[derive(Debug, Args)]
+pub struct BuildArgs {
+    #[command(flatten)]
+    metadata: MetadataArgs,
+    /// Existing checkout of `source_code_snapshot`, it's checked out with `git` into a temporary directory otherwise
+    #[arg(long)]
+    source: Option<Utf8PathBuf>,
+    /// Load `build_environment` image from a local OCI image layout directory or tarball instead of pulling it
+    #[arg(long)]
+    image_archive: Option<Utf8PathBuf>,
+    /// Platform of build container, e.g. `linux/amd64`
+    #[arg(long)]
+    platform: Option<String>,
+    /// Kill build container after this many seconds
+    #[arg(long)]
+    timeout: Option<u64>,
+}
+
+#[derive(Debug, Args)]
+pub struct AttestationArgs {
+    /// On match, write in-toto statement with SLSA provenance predicate to this file
+    #[arg(long)]
+    attestation: Option<Utf8PathBuf>,
+    /// Sign `--attestation` into a DSSE envelope with ed25519 key, file with hex or base64 of its seed
+    #[arg(long, requires = "attestation")]
+    signing_key: Option<Utf8PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct ReportArgs {
+    /// Write verification report to this file, also when the build fails
+    #[arg(long)]
+    report: Option<Utf8PathBuf>,
+    /// `markdown`, `html` or `text`, chosen by extension of `--report` by default
+    #[arg(long, requires = "report")]
+    report_format: Option<String>,
+}
+
+#[derive(Serialize)]
+struct BuildOutput {
+    wasm_path: Utf8PathBuf,
+    sha256_hex: String,
+    sha256_base58: String,
+    image: ImageReport,
+    platform: PlatformReport,
+    build: BuildReport,
+    #[serde(skip)]
+    hash: SHA256Checksum,
+    #[serde(skip)]
+    metadata: ContractSourceMetadata,
+    #[serde(skip)]
+    whitelist: Option<Whitelist>,
+    /// Temporary directory with `wasm_path` of a build of `git` checkout, removed on drop
+    #[serde(skip)]
+    temp_dir: Option<tempfile::TempDir>,
+}
+
+pub fn validate(args: MetadataArgs) -> eyre::Result<Output> {
+    let (metadata, whitelist) = args.load()?;
+    let result = metadata.validate(whitelist);
+    Ok(Output {
+        success: result.is_ok(),
+        json: match result {
+            Ok(()) => serde_json::json!({ "valid": true }),
+            Err(ref err) => serde_json::json!({ "valid": false, "error": format!("{:#}", err) }),
+        },
+        human: match result {
+            Ok(()) => format!("{} is valid", args.metadata).green().to_string(),
+            Err(err) => format!("{} {:?}", "Invalid metadata:".red(), err),
+        },
+    })
+}
+
+pub fn build(
+    args: BuildArgs,
+    tty: bool,
+    json: bool,
+    observer: &SharedObserver,
+) -> eyre::Result<Output> {
+    let mut output = build_inner(&args, tty, json, observer)?;
+    // printed artifact has to outlive the command
+    if let Some(temp_dir) = output.temp_dir.take() {
+        let _ = temp_dir.into_path();
+    }
+    let human = format!(
+        "{} {}\n{} {}\n{} {}",
+        "artifact:".green(),
+        output.wasm_path,
+        "sha256 (hex):".green(),
+        output.sha256_hex,
+        "sha256 (base58):".green(),
+        output.sha256_base58
+    );
+    Ok(Output {
+        success: true,
+        json: serde_json::to_value(&output)?,
+        human,
+    })
+}
+
+pub fn verify(
+    args: BuildArgs,
+    expected_hash: &str,
+    attestation: AttestationArgs,
+    report: ReportArgs,
+    tty: bool,
+    json: bool,
+    observer: &SharedObserver,
+) -> eyre::Result<Output> {
+    let expected_hash = SHA256Checksum::parse(expected_hash)?;
+    let report_format = report
+        .report_format
+        .as_deref()
+        .map(str::parse::<ReportFormat>)
+        .transpose()?;
+    let result = build_inner(&args, tty, json, observer);
+    if let Some(ref path) = report.report {
+        let format = report_format.unwrap_or(ReportFormat::from_path(path));
+        write_report(&args, &expected_hash, &result, path, format)?;
+    }
+    let output = result?;
+    let matches = output.hash == expected_hash;
+    let human = if matches {
+        let mut human = format!(
+            "{} {}",
+            "Artifact hash matches expected one:".green(),
+            output.sha256_base58
+        );
+        if let Some(ref path) = attestation.attestation {
+            write_attestation(
+                &output,
+                &expected_hash,
+                path,
+                attestation.signing_key.as_deref(),
+            )?;
+            human.push_str(&format!("\n{} {}", "attestation:".green(), path));
+        }
+        human
+    } else {
+        format!(
+            "{} {} {} {}",
+            "Artifact hash".red(),
+            output.sha256_base58,
+            "doesn't match expected one".red(),
+            expected_hash.to_base58_string()
+        )
+    };
+    let mut json = serde_json::to_value(&output)?;
+    json["expected_hash"] = expected_hash.to_base58_string().into();
+    json["verdict"] = if matches { "match" } else { "mismatch" }.into();
+    Ok(Output {
+        success: matches,
+        json,
+        human,
+    })
+}
+
+fn write_report(
+    args: &BuildArgs,
+    expected_hash: &SHA256Checksum,
+    result: &eyre::Result<BuildOutput>,
+    path: &camino::Utf8Path,
+    format: ReportFormat,
+) -> eyre::Result<()> {
+    let report = match result {
+        Ok(output) => VerificationReport::new(
+            output.metadata.clone(),
+            WhitelistDecision::check(&output.metadata, output.whitelist.as_ref()),
+            expected_hash,
+            Ok((&output.build, &output.hash)),
+        ),
+        Err(err) => {
+            let (metadata, whitelist) = args.metadata.load()?;
+            let whitelist = WhitelistDecision::check(&metadata, whitelist.as_ref());
+            VerificationReport::new(metadata, whitelist, expected_hash, Err(err))
+        }
+    };
+    std::fs::write(path, report.render(format))
+        .map_err(|err| eyre::eyre!("couldn't write `{}`: {}", path, err))
+}
+
+fn write_attestation(
+    output: &BuildOutput,
+    hash: &SHA256Checksum,
+    path: &camino::Utf8Path,
+    signing_key: Option<&camino::Utf8Path>,
+) -> eyre::Result<()> {
+    let statement = Statement::for_verification(
+        &output.metadata,
+        hash,
+        output.whitelist.as_ref(),
+        Some(&output.build),
+    )?;
+    let content = match signing_key {
+        Some(signing_key) => serde_json::to_vec_pretty(
+            &statement.sign(&attestation::load_signing_key(signing_key)?)?,
+        )?,
+        None => serde_json::to_vec_pretty(&statement)?,
+    };
+    std::fs::write(path, content).map_err(|err| eyre::eyre!("couldn't write `{}`: {}", path, err))
+}
+
+fn build_inner(
+    args: &BuildArgs,
+    tty: bool,
+    json: bool,
+    observer: &SharedObserver,
+) -> eyre::Result<BuildOutput> {
+    let (metadata, whitelist) = args.metadata.load()?;
+    let _span = near_verify_rs::logic::verify_span(&metadata).entered();
+    metadata.validate(whitelist.clone())?;
+    let build_environment = metadata
+        .build_info
+        .as_ref()
+        .expect("cannot be [Option::None] as per `validate` check")
+        .build_environment
+        .clone();
+
+    let daemon = docker_checks::sanity::check_daemon(observer)?;
+    let image_provider = match args.image_archive {
+        Some(ref archive) => ImageProvider::Archive(archive.clone()),
+        None => ImageProvider::Registry,
+    };
+    let image = image_provider.check(&build_environment, args.platform.as_deref(), observer)?;
+    let platform =
+        docker_checks::platform::check(&daemon, &image, args.platform.as_deref(), observer)?;
+
+    let build_options = BuildOptions {
+        image: image_provider.build_image(&image),
+        platform: args.platform.clone(),
+        limits: Limits {
+            timeout: args.timeout.map(Duration::from_secs),
+            ..Default::default()
+        },
+        output: if json {
+            OutputMode::Capture(CaptureOptions::default())
+        } else {
+            OutputMode::Inherit
+        },
+        tty,
+        observer: observer.clone(),
+        ..Default::default()
+    };
+
+    let mut temp_dir = None;
+    let build = match args.source {
+        Some(ref source) => nep330_build::run(metadata.clone(), source.clone(), build_options)?,
+        None => {
+            let source_id = SourceId::from_url(
+                &metadata
+                    .build_info
+                    .as_ref()
+                    .expect("cannot be [Option::None] as per `validate` check")
+                    .source_code_snapshot,
+            )?;
+            let root = tempfile::Builder::new()
+                .prefix("near-verify-rs-")
+                .tempdir()?;
+            let checkouts = Arc::new(GitCheckouts {
+                root: Utf8PathBuf::from_path_buf(root.path().to_path_buf())
+                    .map_err(|path| eyre::eyre!("non utf-8 temp dir {:?}", path))?,
+            });
+            temp_dir = Some(root);
+            let workdir = {
+                let _span = near_verify_rs::logic::fetch_span(&source_id).entered();
+                checkouts.checkout(&source_id)?
+            };
+            // artifact would be removed with checkout, so it's copied out of it first
+            let result = nep330_build::run(metadata.clone(), workdir.clone(), build_options)
+                .and_then(|build| keep_artifact(build, &checkouts.root));
+            checkouts.release(&source_id, &workdir);
+            result?
+        }
+    };
+
+    let hash = near_verify_rs::logic::compute_hash(build.wasm_path.clone())?;
+    Ok(BuildOutput {
+        wasm_path: build.wasm_path.clone(),
+        sha256_hex: hash.to_hex_string(),
+        sha256_base58: hash.to_base58_string(),
+        hash,
+        image,
+        platform,
+        build,
+        metadata,
+        whitelist,
+        temp_dir,
+    })
+}
+
+/// Copies artifact out of temporary checkout into `root`, which checkout is a subdirectory of
+fn keep_artifact(mut build: BuildReport, root: &camino::Utf8Path) -> eyre::Result<BuildReport> {
+    let file_name = build
+        .wasm_path
+        .file_name()
+        .ok_or(eyre::eyre!("artifact path has no file name"))?;
+    let kept = root.join(file_name);
+    std::fs::copy(&build.wasm_path, &kept)?;
+    build.wasm_path = kept;
+    Ok(build)
+}
+
+pub fn check_docker(
+    image: Option<&str>,
+    platform: Option<&str>,
+    observer: &SharedObserver,
+) -> eyre::Result<Output> {
+    #[derive(Serialize)]
+    struct CheckDockerOutput {
+        daemon: DaemonReport,
+        image: Option<ImageReport>,
+        platform: Option<PlatformReport>,
+    }
+
+    let daemon = docker_checks::sanity::check_daemon(observer)?;
+    let (image, platform_report) = match image {
+        Some(image) => {
+            let image = docker_checks::pull_image::check_platform(image, platform, observer)?;
+            let platform_report =
+                docker_checks::platform::check(&daemon, &image, platform, observer)?;
+            (Some(image), Some(platform_report))
+        }
+        None => (None, None),
+    };
+    let mut human = format!(
+        "{} docker {} ({}, {}/{}{})",
+        "Docker daemon is reachable:".green(),
+        daemon.server_version,
+        daemon.storage_driver,
+        daemon.os_type,
+        daemon.architecture,
+        if daemon.rootless { ", rootless" } else { "" }
+    );
+    if let Some(ref image) = image {
+        human.push_str(&format!(
+            "\n{} {} ({}, {})",
+            "Image is verified:".green(),
+            image.reference,
+            image.id,
+            image.platform
+        ));
+    }
+    let output = CheckDockerOutput {
+        daemon,
+        image,
+        platform: platform_report,
+    };
+    Ok(Output {
+        success: true,
+        json: serde_json::to_value(&output)?,
+        human,
+    })
+}
@@ -0,0 +1,127 @@
+use std::io::IsTerminal;
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+use colored::Colorize;
+use near_verify_rs::logic::events::{Console, SharedObserver, Silent};
+
+mod commands;
+
+/// Verifies reproducible builds of NEAR contracts from their NEP-330 source metadata
+#[derive(Debug, Parser)]
+#[command(name = "near-verify-rs", version)]
+struct Cli {
+    /// Print a single JSON document to stdout instead of human-readable output
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Validate contract source metadata JSON file
+    Validate(commands::MetadataArgs),
+    /// Build contract and compare hash of artifact with expected one
+    Verify {
+        #[command(flatten)]
+        build: commands::BuildArgs,
+        /// sha256 of deployed contract's code, base58 (as displayed by explorers) or hex
+        #[arg(long)]
+        expected_hash: String,
+        #[command(flatten)]
+        attestation: commands::AttestationArgs,
+        #[command(flatten)]
+        report: commands::ReportArgs,
+    },
+    /// Build contract and print path and hashes of artifact
+    Build(commands::BuildArgs),
+    /// Whitelist files
+    Whitelist {
+        #[command(subcommand)]
+        command: WhitelistCommand,
+    },
+    /// Check that docker daemon is reachable, and optionally pull and verify a `build_environment` image
+    CheckDocker {
+        /// `build_environment` image, e.g. `sourcescan/cargo-near:0.13.4-rust-1.85.0@sha256:a9d8...`
+        #[arg(long)]
+        image: Option<String>,
+        /// Platform to pull image for, e.g. `linux/amd64`
+        #[arg(long)]
+        platform: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum WhitelistCommand {
+    /// Check whitelist file for invalid, duplicate and overlapping entries
+    Lint {
+        /// Policy file (JSON or TOML) or legacy JSON list of allowed images and command prefixes
+        whitelist: camino::Utf8PathBuf,
+    },
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
+        .init();
+
+    // progress output would break JSON on stdout
+    let observer = if cli.json {
+        SharedObserver::new(Silent)
+    } else {
+        SharedObserver::new(Console)
+    };
+    // container output would break JSON on stdout, and a tty is only useful for a human
+    let tty = !cli.json && std::io::stdin().is_terminal();
+    let result = match cli.command {
+        Command::Validate(args) => commands::validate(args),
+        Command::Verify {
+            build,
+            expected_hash,
+            attestation,
+            report,
+        } => commands::verify(
+            build,
+            &expected_hash,
+            attestation,
+            report,
+            tty,
+            cli.json,
+            &observer,
+        ),
+        Command::Build(args) => commands::build(args, tty, cli.json, &observer),
+        Command::Whitelist {
+            command: WhitelistCommand::Lint { whitelist },
+        } => commands::lint_whitelist(&whitelist),
+        Command::CheckDocker { image, platform } => {
+            commands::check_docker(image.as_deref(), platform.as_deref(), &observer)
+        }
+    };
+
+    match result {
+        Ok(output) => {
+            if cli.json {
+                println!("{}", output.json);
+            } else {
+                println!();
+                println!("{}", output.human);
+            }
+            if output.success {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+        Err(err) => {
+            if cli.json {
+                println!("{}", serde_json::json!({ "error": format!("{:#}", err) }));
+            } else {
+                eprintln!("{} {:?}", "Error:".red(), err);
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -16,5 +16,8 @@ pub const LINK: &str = "NEP330_LINK";
 pub const VERSION: &str = "NEP330_VERSION";
 // ====================== End section =======================================
 pub mod nonspec {
+    #[deprecated(
+        note = "isn't read anymore, build container tty is controlled with `BuildOptions::tty`, off by default"
+    )]
     pub const SERVER_DISABLE_INTERACTIVE: &str = "CARGO_NEAR_SERVER_BUILD_DISABLE_INTERACTIVE";
 }
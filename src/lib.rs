@@ -1,5 +1,7 @@
 pub mod types {
     pub mod contract_source_metadata;
+    pub mod policy;
+    pub mod report;
     pub mod source_id;
     pub mod whitelist;
 
@@ -14,6 +16,8 @@ pub mod types {
         pub mod legacy_rust {
             pub mod manifest_path;
             pub mod metadata;
+            mod target_dir;
+            mod workspace;
 
             pub use manifest_path::ManifestPath;
             pub use metadata::CrateMetadata;
@@ -30,19 +34,75 @@ pub mod logic {
     }
     pub mod nep330_build;
     pub mod docker_checks {
+        use crate::logic::internal::docker_command::command_failed;
         use crate::logic::internal::docker_command::handle_io_error;
-        use crate::logic::internal::docker_command::print;
 
+        pub mod image_provider;
+        pub mod load_image;
+        pub mod platform;
         pub mod pull_image;
         pub mod sanity;
+
+        pub use image_provider::ImageProvider;
+    }
+
+    /// async variants of [nep330_build] and [docker_checks] functions, returned futures
+    /// can be cancelled by dropping them
+    #[cfg(feature = "tokio")]
+    pub mod nonblocking {
+        pub mod nep330_build;
+        pub mod docker_checks {
+            pub mod image_provider;
+            pub mod pull_image;
+            pub mod sanity;
+        }
     }
 
+    /// [nep330_build] and [docker_checks] functions, implemented with requests to
+    /// Docker Engine API over unix socket instead of `docker` CLI
+    #[cfg(all(unix, feature = "engine-api"))]
+    pub mod engine_api {
+        pub mod client;
+        pub mod nep330_build;
+        pub mod docker_checks {
+            pub mod pull_image;
+            pub mod sanity;
+        }
+
+        pub use client::{ApiError, Client};
+    }
+
+    pub mod attestation;
+    pub mod batch;
+    pub mod events;
+    pub mod result_cache;
+
     pub(crate) mod internal {
         pub mod docker_command;
     }
+    /// Root `verify` [tracing::Span] of a contract, which spans of verification stages
+    /// (`validate`, `fetch`, `pull`, `build`, `locate`, `hash`) are to be nested in
+    ///
+    /// All of them but `fetch` are entered by the library, source code is checked out
+    /// by the caller in [fetch_span]
+    pub fn verify_span(
+        contract_source_metadata: &crate::types::contract_source_metadata::ContractSourceMetadata,
+    ) -> tracing::Span {
+        let build_info = contract_source_metadata.build_info.as_ref();
+        tracing::info_span!(
+            "verify",
+            snapshot = build_info.map(|build_info| build_info.source_code_snapshot.as_str()),
+            contract_path = build_info.map(|build_info| build_info.contract_path.as_str()),
+        )
+    }
+    /// `fetch` [tracing::Span] of checking out source code snapshot of a contract
+    pub fn fetch_span(source_id: &crate::types::source_id::SourceId) -> tracing::Span {
+        tracing::info_span!("fetch", snapshot = %source_id.as_url())
+    }
     pub fn compute_hash(
         path: camino::Utf8PathBuf,
     ) -> eyre::Result<crate::types::sha256_checksum::SHA256Checksum> {
+        let _span = tracing::info_span!("hash", path = %path).entered();
         let mut hasher = <sha2::Sha256 as sha2::Digest>::new();
         sha2::Digest::update(&mut hasher, std::fs::read(&path)?);
         let hash = sha2::Digest::finalize(hasher);
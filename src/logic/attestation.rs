@@ -0,0 +1,382 @@
+use std::collections::BTreeMap;
+
+use base64::Engine;
+use camino::Utf8Path;
+use ed25519_dalek::Signer;
+use serde::{Deserialize, Serialize};
+
+use crate::types::contract_source_metadata::{docker_image_digest, ContractSourceMetadata};
+use crate::types::report::BuildReport;
+use crate::types::sha256_checksum::SHA256Checksum;
+use crate::types::source_id::{GitReference, SourceId, SourceKind};
+use crate::types::whitelist::{self, Whitelist};
+
+pub const STATEMENT_TYPE: &str = "https://in-toto.io/Statement/v1";
+pub const PROVENANCE_PREDICATE_TYPE: &str = "https://slsa.dev/provenance/v1";
+/// `buildType` of [BuildDefinition], builds are described by NEP330 `build_info`
+pub const BUILD_TYPE: &str = "https://github.com/near/NEPs/blob/master/neps/nep-0330.md";
+/// `builder.id` of [RunDetails], as [purl](https://github.com/package-url/purl-spec)
+pub const BUILDER_ID: &str = "pkg:cargo/near-verify-rs";
+/// `payloadType` of [Envelope]
+pub const DSSE_PAYLOAD_TYPE: &str = "application/vnd.in-toto+json";
+
+/// Subject name, used when the artifact's file name is unknown, e.g. for cached results
+const DEFAULT_SUBJECT_NAME: &str = "contract.wasm";
+
+/// [in-toto Statement](https://github.com/in-toto/attestation/blob/main/spec/v1/statement.md)
+/// with [SLSA provenance](https://slsa.dev/spec/v1.0/provenance) predicate,
+/// attesting that a wasm artifact was reproduced from its NEP330 metadata
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Statement {
+    #[serde(rename = "_type")]
+    pub type_: String,
+    pub subject: Vec<ResourceDescriptor>,
+    pub predicate_type: String,
+    pub predicate: Provenance,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceDescriptor {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+    /// Algorithm to lowercase hex digest, e.g. `sha256` or `gitCommit`
+    pub digest: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Provenance {
+    pub build_definition: BuildDefinition,
+    pub run_details: RunDetails,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildDefinition {
+    pub build_type: String,
+    pub external_parameters: ExternalParameters,
+    pub internal_parameters: InternalParameters,
+    /// Source repository at its commit and `build_environment` image at its digest
+    pub resolved_dependencies: Vec<ResourceDescriptor>,
+}
+
+/// Fields of NEP330 `build_info`, which determine the build
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternalParameters {
+    /// `source_code_snapshot`, [SourceId] url
+    pub source: String,
+    pub build_environment: String,
+    pub build_command: Vec<String>,
+    pub contract_path: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InternalParameters {
+    /// sha256 hex of compact json of sorted whitelist entries, which metadata was validated against
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub whitelist_digest: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunDetails {
+    pub builder: Builder,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<RunMetadata>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Builder {
+    pub id: String,
+    /// `near-verify-rs` to its version
+    pub version: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunMetadata {
+    /// Name of build container
+    pub invocation_id: String,
+}
+
+impl Statement {
+    /// Statement for an artifact with `wasm_hash`, which matched the expected hash
+    ///
+    /// `build` is [Option::None] for results, which weren't built in this run (e.g. cached ones)
+    pub fn for_verification(
+        contract_source_metadata: &ContractSourceMetadata,
+        wasm_hash: &SHA256Checksum,
+        whitelist: Option<&Whitelist>,
+        build: Option<&BuildReport>,
+    ) -> eyre::Result<Self> {
+        let build_info = contract_source_metadata
+            .build_info
+            .as_ref()
+            .ok_or(eyre::eyre!(
+                "`build_info` field of `ContractSourceMetadata` cannot be null"
+            ))?;
+        let source_id = SourceId::from_url(&build_info.source_code_snapshot)?;
+        let SourceKind::Git(GitReference::Rev(ref commit)) = *source_id.kind();
+        let image_digest = docker_image_digest(&build_info.build_environment)?;
+        let (image_digest_algorithm, image_digest) = image_digest
+            .split_once(':')
+            .expect("digest is `sha256:<hex>` as per regex");
+
+        let whitelist_digest = whitelist
+            .map(|whitelist| -> eyre::Result<String> {
+                Ok(hex::encode(<sha2::Sha256 as sha2::Digest>::digest(
+                    serde_json::to_vec(&whitelist::sorted(whitelist))?,
+                )))
+            })
+            .transpose()?;
+        let subject_name = build
+            .and_then(|build| build.wasm_path.file_name())
+            .unwrap_or(DEFAULT_SUBJECT_NAME);
+
+        Ok(Self {
+            type_: STATEMENT_TYPE.to_string(),
+            subject: vec![ResourceDescriptor {
+                name: Some(subject_name.to_string()),
+                uri: None,
+                digest: BTreeMap::from([("sha256".to_string(), wasm_hash.to_hex_string())]),
+            }],
+            predicate_type: PROVENANCE_PREDICATE_TYPE.to_string(),
+            predicate: Provenance {
+                build_definition: BuildDefinition {
+                    build_type: BUILD_TYPE.to_string(),
+                    external_parameters: ExternalParameters {
+                        source: build_info.source_code_snapshot.clone(),
+                        build_environment: build_info.build_environment.clone(),
+                        build_command: build_info.build_command.clone(),
+                        contract_path: build_info.contract_path.clone(),
+                    },
+                    internal_parameters: InternalParameters { whitelist_digest },
+                    resolved_dependencies: vec![
+                        ResourceDescriptor {
+                            name: None,
+                            uri: Some(format!("git+{}", source_id.url())),
+                            digest: BTreeMap::from([("gitCommit".to_string(), commit.clone())]),
+                        },
+                        ResourceDescriptor {
+                            name: None,
+                            uri: Some(build_info.build_environment.clone()),
+                            digest: BTreeMap::from([(
+                                image_digest_algorithm.to_string(),
+                                image_digest.to_string(),
+                            )]),
+                        },
+                    ],
+                },
+                run_details: RunDetails {
+                    builder: Builder {
+                        id: BUILDER_ID.to_string(),
+                        version: BTreeMap::from([(
+                            env!("CARGO_PKG_NAME").to_string(),
+                            env!("CARGO_PKG_VERSION").to_string(),
+                        )]),
+                    },
+                    metadata: build.map(|build| RunMetadata {
+                        invocation_id: build.container_name.clone(),
+                    }),
+                },
+            },
+        })
+    }
+
+    /// Wraps statement into a [DSSE](https://github.com/secure-systems-lab/dsse) envelope,
+    /// signed with `signing_key`
+    pub fn sign(&self, signing_key: &ed25519_dalek::SigningKey) -> eyre::Result<Envelope> {
+        let payload = serde_json::to_vec(self)?;
+        let signature = signing_key.sign(&pae(DSSE_PAYLOAD_TYPE, &payload));
+        Ok(Envelope {
+            payload: base64::engine::general_purpose::STANDARD.encode(&payload),
+            payload_type: DSSE_PAYLOAD_TYPE.to_string(),
+            signatures: vec![EnvelopeSignature {
+                keyid: key_id(&signing_key.verifying_key()),
+                sig: base64::engine::general_purpose::STANDARD.encode(signature.to_bytes()),
+            }],
+        })
+    }
+}
+
+/// [DSSE envelope](https://github.com/secure-systems-lab/dsse/blob/master/envelope.md) of a [Statement]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Envelope {
+    /// base64 of statement json
+    pub payload: String,
+    pub payload_type: String,
+    pub signatures: Vec<EnvelopeSignature>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnvelopeSignature {
+    /// sha256 hex of ed25519 public key
+    pub keyid: String,
+    /// base64 of ed25519 signature
+    pub sig: String,
+}
+
+impl Envelope {
+    /// Checks that envelope has a signature of `verifying_key` and returns its [Statement]
+    pub fn verify(&self, verifying_key: &ed25519_dalek::VerifyingKey) -> eyre::Result<Statement> {
+        if self.payload_type != DSSE_PAYLOAD_TYPE {
+            return Err(eyre::eyre!(
+                "unexpected payload type `{}`",
+                self.payload_type
+            ));
+        }
+        let payload = base64::engine::general_purpose::STANDARD
+            .decode(&self.payload)
+            .map_err(|err| eyre::eyre!("malformed payload: {}", err))?;
+        let message = pae(&self.payload_type, &payload);
+        let verified = self.signatures.iter().any(|signature| {
+            base64::engine::general_purpose::STANDARD
+                .decode(&signature.sig)
+                .ok()
+                .and_then(|bytes| ed25519_dalek::Signature::from_slice(&bytes).ok())
+                .is_some_and(|sig| verifying_key.verify_strict(&message, &sig).is_ok())
+        });
+        if !verified {
+            return Err(eyre::eyre!(
+                "envelope has no valid signature of key `{}`",
+                key_id(verifying_key)
+            ));
+        }
+        Ok(serde_json::from_slice(&payload)?)
+    }
+}
+
+/// Loads ed25519 signing key from a file with hex or base64 of its 32-byte seed
+pub fn load_signing_key(path: &Utf8Path) -> eyre::Result<ed25519_dalek::SigningKey> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|err| eyre::eyre!("couldn't read `{}`: {}", path, err))?;
+    let content = content.trim();
+    let seed = match hex::decode(content) {
+        Ok(seed) => seed,
+        Err(_) => base64::engine::general_purpose::STANDARD
+            .decode(content)
+            .map_err(|err| eyre::eyre!("`{}` is neither hex nor base64: {}", path, err))?,
+    };
+    let seed: [u8; ed25519_dalek::SECRET_KEY_LENGTH] = seed.try_into().map_err(|_| {
+        eyre::eyre!(
+            "`{}` has to contain a {}-byte ed25519 seed",
+            path,
+            ed25519_dalek::SECRET_KEY_LENGTH
+        )
+    })?;
+    Ok(ed25519_dalek::SigningKey::from_bytes(&seed))
+}
+
+fn key_id(verifying_key: &ed25519_dalek::VerifyingKey) -> String {
+    hex::encode(<sha2::Sha256 as sha2::Digest>::digest(
+        verifying_key.as_bytes(),
+    ))
+}
+
+/// DSSE pre-authentication encoding, which is what's actually signed
+fn pae(payload_type: &str, payload: &[u8]) -> Vec<u8> {
+    let mut message = format!(
+        "DSSEv1 {} {} {} ",
+        payload_type.len(),
+        payload_type,
+        payload.len()
+    )
+    .into_bytes();
+    message.extend_from_slice(payload);
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::contract_source_metadata::ContractSourceMetadata;
+    use crate::types::sha256_checksum::SHA256Checksum;
+    use crate::types::whitelist::Whitelist;
+
+    use super::Statement;
+
+    fn metadata() -> ContractSourceMetadata {
+        serde_json::from_str(
+            r#"{
+  "build_info": {
+    "build_command": ["cargo", "near", "build", "non-reproducible-wasm", "--locked"],
+    "build_environment": "sourcescan/cargo-near:0.13.4-rust-1.85.0@sha256:a9d8bee7b134856cc8baa142494a177f2ba9ecfededfcdd38f634e14cca8aae2",
+    "contract_path": "",
+    "source_code_snapshot": "git+https://github.com/dj8yfo/verify_contracts_collection?rev=e3303f0cf8761b99a84d6b4a2f5e2e7f5bbe1e9a"
+  },
+  "link": null,
+  "standards": [],
+  "version": "0.1.0"
+}"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_statement_fields() {
+        let hash = SHA256Checksum { hash: vec![1; 32] };
+        let statement = Statement::for_verification(&metadata(), &hash, None, None).unwrap();
+        let json = serde_json::to_value(&statement).unwrap();
+        assert_eq!(json["_type"], super::STATEMENT_TYPE);
+        assert_eq!(json["subject"][0]["digest"]["sha256"], hash.to_hex_string());
+        let definition = &json["predicate"]["buildDefinition"];
+        assert_eq!(
+            definition["resolvedDependencies"][0]["digest"]["gitCommit"],
+            "e3303f0cf8761b99a84d6b4a2f5e2e7f5bbe1e9a"
+        );
+        assert_eq!(
+            definition["resolvedDependencies"][1]["digest"]["sha256"],
+            "a9d8bee7b134856cc8baa142494a177f2ba9ecfededfcdd38f634e14cca8aae2"
+        );
+        assert!(definition["internalParameters"]["whitelistDigest"].is_null());
+    }
+
+    #[test]
+    fn test_whitelist_digest_ignores_order() {
+        let hash = SHA256Checksum { hash: vec![1; 32] };
+        let mut whitelist: Whitelist = serde_json::from_str(
+            r#"[
+  {"expected_docker_image": "sourcescan/cargo-near", "expected_command_prefix": ["cargo", "near"]},
+  {"expected_docker_image": "docker.io/sourcescan/cargo-near", "expected_command_prefix": ["cargo"]}
+]"#,
+        )
+        .unwrap();
+        let digest = |whitelist: &Whitelist| {
+            Statement::for_verification(&metadata(), &hash, Some(whitelist), None)
+                .unwrap()
+                .predicate
+                .build_definition
+                .internal_parameters
+                .whitelist_digest
+        };
+        let expected = digest(&whitelist);
+        whitelist.reverse();
+        assert_eq!(digest(&whitelist), expected);
+    }
+
+    #[test]
+    fn test_dsse_sign_and_verify() {
+        let hash = SHA256Checksum { hash: vec![1; 32] };
+        let statement =
+            Statement::for_verification(&metadata(), &hash, Some(&vec![]), None).unwrap();
+        let signing_key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let envelope = statement.sign(&signing_key).unwrap();
+        assert_eq!(
+            envelope.verify(&signing_key.verifying_key()).unwrap(),
+            statement
+        );
+
+        let other = ed25519_dalek::SigningKey::from_bytes(&[8; 32]);
+        let err = envelope
+            .verify(&other.verifying_key())
+            .expect_err("signed with another key");
+        assert!(format!("{:?}", err).contains("has no valid signature"));
+    }
+}
@@ -0,0 +1,383 @@
+use std::collections::VecDeque;
+use std::sync::{mpsc, Arc, Mutex};
+
+use serde::Serialize;
+
+use crate::logic::events::SharedObserver;
+use crate::logic::nep330_build::{self, BuildOptions};
+use crate::logic::result_cache::ResultCache;
+use crate::types::contract_source_metadata::ContractSourceMetadata;
+use crate::types::report::BuildReport;
+use crate::types::sha256_checksum::SHA256Checksum;
+use crate::types::source_id::SourceId;
+use crate::types::whitelist::Whitelist;
+
+mod checkout;
+
+pub use crate::types::report::Verdict;
+pub use checkout::{Checkouts, GitCheckouts};
+
+/// Contract to be verified by [run]
+#[derive(Debug, Clone)]
+pub struct Job {
+    /// Identifier of job in its [JobReport], e.g. account id of contract
+    pub label: String,
+    pub metadata: ContractSourceMetadata,
+    /// sha256 of deployed contract's code
+    pub expected_hash: SHA256Checksum,
+}
+
+pub struct BatchOptions {
+    /// Maximum number of builds running at the same time
+    pub concurrency: usize,
+    /// Whitelist, which every job's metadata is validated against
+    pub whitelist: Option<Whitelist>,
+    /// Options of every build, [OutputMode::Capture](nep330_build::OutputMode::Capture)
+    /// keeps output of concurrent builds from interleaving
+    pub build_options: BuildOptions,
+    pub checkouts: Arc<dyn Checkouts>,
+    /// Results of previous verifications, builds with a cached result are skipped,
+    /// it's pruned once at the start of [run]
+    pub result_cache: Option<ResultCache>,
+    /// Creates observer of a build from the first of jobs sharing it, so that events of concurrent
+    /// builds can be told apart, [BuildOptions::observer] is shared by all builds if unset
+    pub job_observer: Option<Arc<JobObserver>>,
+}
+
+/// Factory of per-build observers, see [BatchOptions::job_observer]
+pub type JobObserver = dyn Fn(&Job) -> SharedObserver + Send + Sync;
+
+/// Result of a [Job], sent as soon as it's known
+#[derive(Debug, Clone, Serialize)]
+pub struct JobReport {
+    pub label: String,
+    pub source_code_snapshot: Option<String>,
+    pub verdict: Verdict,
+    /// base58 of expected hash
+    pub expected_hash: String,
+    /// base58 of hash of built artifact
+    pub actual_hash: Option<String>,
+    /// Result was taken from [BatchOptions::result_cache] without building
+    pub cached: bool,
+    /// Result was shared with an earlier job with identical metadata
+    pub deduplicated: bool,
+    pub build: Option<BuildReport>,
+    pub error: Option<String>,
+}
+
+/// Jobs with the same `source_code_snapshot`, which share a checkout and run one after another
+struct Group {
+    source_id: Option<SourceId>,
+    /// Jobs with identical metadata, which are built once
+    builds: Vec<Vec<Job>>,
+}
+
+/// Reports of a batch, in order of completion
+///
+/// Builds keep running in background threads if this is dropped early
+pub struct BatchRun {
+    receiver: mpsc::Receiver<JobReport>,
+}
+
+impl Iterator for BatchRun {
+    type Item = JobReport;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.recv().ok()
+    }
+}
+
+/// Verifies `jobs` with at most [BatchOptions::concurrency] builds running at a time
+///
+/// Jobs are grouped by `source_code_snapshot`: each snapshot is checked out once, and jobs with
+/// identical metadata are built once. Groups are processed in parallel, builds of a group
+/// run sequentially in its checkout.
+pub fn run(jobs: Vec<Job>, options: BatchOptions) -> BatchRun {
+    let (sender, receiver) = mpsc::channel();
+    if let Some(ref result_cache) = options.result_cache {
+        if let Err(err) = result_cache.prune() {
+            tracing::warn!(
+                "couldn't prune result cache `{}`: {:#}",
+                result_cache.dir(),
+                err
+            );
+        }
+    }
+    let groups = plan(jobs);
+    let workers = options.concurrency.max(1).min(groups.len());
+    let groups = Arc::new(Mutex::new(groups));
+    let options = Arc::new(options);
+    for _ in 0..workers {
+        let groups = groups.clone();
+        let options = options.clone();
+        let sender = sender.clone();
+        std::thread::spawn(move || loop {
+            let Some(group) = groups
+                .lock()
+                .expect("no panics while holding the lock")
+                .pop_front()
+            else {
+                break;
+            };
+            run_group(group, &options, &sender);
+        });
+    }
+    BatchRun { receiver }
+}
+
+fn plan(jobs: Vec<Job>) -> VecDeque<Group> {
+    let mut groups: VecDeque<Group> = VecDeque::new();
+    for job in jobs {
+        let source_id = job
+            .metadata
+            .build_info
+            .as_ref()
+            .and_then(|build_info| SourceId::from_url(&build_info.source_code_snapshot).ok());
+        let group = match groups
+            .iter_mut()
+            .find(|group| source_id.is_some() && group.source_id == source_id)
+        {
+            Some(group) => group,
+            None => {
+                groups.push_back(Group {
+                    source_id,
+                    builds: vec![],
+                });
+                groups.back_mut().expect("just pushed")
+            }
+        };
+        match group
+            .builds
+            .iter_mut()
+            .find(|build| build[0].metadata == job.metadata)
+        {
+            Some(build) => build.push(job),
+            None => group.builds.push(vec![job]),
+        }
+    }
+    groups
+}
+
+/// Artifact hash and build report of one metadata
+struct Outcome {
+    hash: SHA256Checksum,
+    cached: bool,
+    build: Option<BuildReport>,
+}
+
+fn run_group(group: Group, options: &BatchOptions, sender: &mpsc::Sender<JobReport>) {
+    let mut checkout: Option<eyre::Result<camino::Utf8PathBuf>> = None;
+    for jobs in group.builds {
+        let outcome = build(&jobs[0], &group.source_id, &mut checkout, options);
+        for (index, job) in jobs.into_iter().enumerate() {
+            let report = job_report(job, &outcome, index > 0);
+            if sender.send(report).is_err() {
+                tracing::debug!("batch reports receiver dropped");
+            }
+        }
+    }
+    if let (Some(source_id), Some(Ok(path))) = (group.source_id, checkout) {
+        options.checkouts.release(&source_id, &path);
+    }
+}
+
+fn build(
+    job: &Job,
+    source_id: &Option<SourceId>,
+    checkout: &mut Option<eyre::Result<camino::Utf8PathBuf>>,
+    options: &BatchOptions,
+) -> eyre::Result<Outcome> {
+    let metadata = &job.metadata;
+    let _span = crate::logic::verify_span(metadata).entered();
+    metadata.validate(options.whitelist.clone())?;
+    let key = ResultCache::key(metadata, options.whitelist.as_ref(), &options.build_options)?;
+    if let Some(ref result_cache) = options.result_cache {
+        if let Some(entry) = result_cache.get(&key)? {
+            return Ok(Outcome {
+                hash: entry.checksum()?,
+                cached: true,
+                build: None,
+            });
+        }
+    }
+
+    let source_id = source_id
+        .as_ref()
+        .ok_or(eyre::eyre!("invalid `source_code_snapshot`"))?;
+    let workdir = checkout
+        .get_or_insert_with(|| {
+            let _span = crate::logic::fetch_span(source_id).entered();
+            options.checkouts.checkout(source_id)
+        })
+        .as_ref()
+        .map_err(|err| eyre::eyre!("checkout failed: {:#}", err))?
+        .clone();
+    let mut build_options = options.build_options.clone();
+    if let Some(ref job_observer) = options.job_observer {
+        build_options.observer = job_observer(job);
+    }
+    let build_report = nep330_build::run(metadata.clone(), workdir, build_options)?;
+    let hash = crate::logic::compute_hash(build_report.wasm_path.clone())?;
+    if let Some(ref result_cache) = options.result_cache {
+        result_cache.insert(&key, metadata, &hash)?;
+    }
+    Ok(Outcome {
+        hash,
+        cached: false,
+        build: Some(build_report),
+    })
+}
+
+fn job_report(job: Job, outcome: &eyre::Result<Outcome>, deduplicated: bool) -> JobReport {
+    let source_code_snapshot = job
+        .metadata
+        .build_info
+        .as_ref()
+        .map(|build_info| build_info.source_code_snapshot.clone());
+    let mut report = JobReport {
+        label: job.label,
+        source_code_snapshot,
+        verdict: Verdict::Failed,
+        expected_hash: job.expected_hash.to_base58_string(),
+        actual_hash: None,
+        cached: false,
+        deduplicated,
+        build: None,
+        error: None,
+    };
+    match outcome {
+        Ok(outcome) => {
+            report.verdict = if outcome.hash == job.expected_hash {
+                Verdict::Match
+            } else {
+                Verdict::Mismatch
+            };
+            report.actual_hash = Some(outcome.hash.to_base58_string());
+            report.cached = outcome.cached;
+            report.build = outcome.build.clone();
+        }
+        Err(err) => report.error = Some(format!("{:#}", err)),
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use camino::Utf8PathBuf;
+
+    use super::{BatchOptions, Checkouts, Job, Verdict};
+    use crate::logic::result_cache::ResultCache;
+    use crate::types::sha256_checksum::SHA256Checksum;
+    use crate::types::source_id::SourceId;
+
+    struct NoCheckouts;
+
+    impl Checkouts for NoCheckouts {
+        fn checkout(&self, _source_id: &SourceId) -> eyre::Result<Utf8PathBuf> {
+            Err(eyre::eyre!("no network in tests"))
+        }
+    }
+
+    fn job(label: &str, rev: &str, contract_path: &str, expected_hash: u8) -> Job {
+        Job {
+            label: label.into(),
+            metadata: serde_json::from_str(&format!(
+                r#"{{
+  "build_info": {{
+    "build_command": ["cargo", "near", "build", "non-reproducible-wasm", "--locked"],
+    "build_environment": "sourcescan/cargo-near:0.13.4-rust-1.85.0@sha256:a9d8bee7b134856cc8baa142494a177f2ba9ecfededfcdd38f634e14cca8aae2",
+    "contract_path": "{}",
+    "source_code_snapshot": "git+https://github.com/dj8yfo/verify_contracts_collection?rev={}"
+  }},
+  "link": null,
+  "standards": [{{"standard": "nep330", "version": "1.2.0"}}],
+  "version": "1.0.0"
+}}"#,
+                contract_path, rev
+            ))
+            .unwrap(),
+            expected_hash: SHA256Checksum {
+                hash: vec![expected_hash; 32],
+            },
+        }
+    }
+
+    #[test]
+    fn test_plan_groups_by_snapshot() {
+        let rev_a = "e3303f0cf8761b99f84f93c3a2d7046be6f4edb5";
+        let rev_b = "6fc35ed210d3578b301e25b3b8c11fb53767d032";
+        let groups = super::plan(vec![
+            job("factory", rev_a, "", 1),
+            job("product", rev_a, "product", 1),
+            job("other", rev_b, "", 1),
+            job("factory-copy", rev_a, "", 2),
+        ]);
+        assert_eq!(groups.len(), 2);
+        let labels = groups[0]
+            .builds
+            .iter()
+            .map(|jobs| {
+                jobs.iter()
+                    .map(|job| job.label.as_str())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            labels,
+            vec![vec!["factory", "factory-copy"], vec!["product"]]
+        );
+    }
+
+    #[test]
+    fn test_cached_and_failed_jobs() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let result_cache =
+            ResultCache::open(Utf8PathBuf::from_path_buf(tempdir.path().to_path_buf()).unwrap())
+                .unwrap();
+        let rev = "e3303f0cf8761b99f84f93c3a2d7046be6f4edb5";
+        let cached = job("cached", rev, "", 1);
+        let key = ResultCache::key(&cached.metadata, None, &Default::default()).unwrap();
+        result_cache
+            .insert(&key, &cached.metadata, &cached.expected_hash)
+            .unwrap();
+
+        let mut reports = super::run(
+            vec![
+                cached,
+                job("cached-mismatch", rev, "", 2),
+                job("uncached", rev, "product", 1),
+            ],
+            BatchOptions {
+                concurrency: 4,
+                whitelist: None,
+                build_options: Default::default(),
+                checkouts: Arc::new(NoCheckouts),
+                result_cache: Some(result_cache),
+                job_observer: None,
+            },
+        )
+        .collect::<Vec<_>>();
+        reports.sort_by(|a, b| a.label.cmp(&b.label));
+
+        let verdicts = reports
+            .iter()
+            .map(|report| (report.label.as_str(), report.verdict, report.cached))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            verdicts,
+            vec![
+                ("cached", Verdict::Match, true),
+                ("cached-mismatch", Verdict::Mismatch, true),
+                ("uncached", Verdict::Failed, false),
+            ]
+        );
+        assert!(reports[1].deduplicated);
+        assert!(reports[2]
+            .error
+            .as_ref()
+            .unwrap()
+            .contains("no network in tests"));
+    }
+}
@@ -0,0 +1,111 @@
+use std::process::Command;
+
+use camino::Utf8PathBuf;
+
+use crate::types::source_id::{GitReference, SourceId, SourceKind};
+
+/// Provider of source code checkouts for [super::run]
+///
+/// A checkout is shared by all jobs with the same `source_code_snapshot`
+pub trait Checkouts: Send + Sync {
+    /// Checks out `source_id` and returns its directory
+    fn checkout(&self, source_id: &SourceId) -> eyre::Result<Utf8PathBuf>;
+
+    /// Called after all jobs of `source_id` have finished
+    fn release(&self, _source_id: &SourceId, _path: &Utf8PathBuf) {}
+}
+
+/// Checkouts with `git` CLI in subdirectories of `root`, which are removed on release
+///
+/// Only the commit of snapshot is fetched, with submodules
+#[derive(Debug, Clone)]
+pub struct GitCheckouts {
+    pub root: Utf8PathBuf,
+}
+
+impl GitCheckouts {
+    fn run(command: &mut Command) -> eyre::Result<()> {
+        let program = command.get_program().to_string_lossy().to_string();
+        let output = command.output().map_err(|err| match err.kind() {
+            std::io::ErrorKind::NotFound => eyre::eyre!(
+                "checkout of source code snapshot failed: `{}` not found in PATH",
+                program
+            ),
+            _ => eyre::eyre!(
+                "checkout of source code snapshot failed: `{:?}` couldn't be run: {}",
+                command,
+                err
+            ),
+        })?;
+        if !output.status.success() {
+            return Err(eyre::eyre!(
+                "`{:?}` failed with {}: {}",
+                command,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Checkouts for GitCheckouts {
+    fn checkout(&self, source_id: &SourceId) -> eyre::Result<Utf8PathBuf> {
+        let SourceKind::Git(GitReference::Rev(rev)) = source_id.kind();
+        let dir_name = hex::encode(<sha2::Sha256 as sha2::Digest>::digest(
+            source_id.as_url().to_string(),
+        ));
+        let path = self.root.join(dir_name);
+        if path.exists() {
+            std::fs::remove_dir_all(&path)?;
+        }
+        std::fs::create_dir_all(&path)?;
+
+        let git = |args: &[&str]| {
+            let mut command = Command::new("git");
+            command.arg("-C").arg(&path).args(args);
+            command
+        };
+        Self::run(&mut git(&["init", "--quiet"]))?;
+        Self::run(&mut git(&[
+            "fetch",
+            "--quiet",
+            "--depth",
+            "1",
+            source_id.url().as_str(),
+            rev,
+        ]))?;
+        Self::run(&mut git(&["checkout", "--quiet", "FETCH_HEAD"]))?;
+        Self::run(&mut git(&[
+            "submodule",
+            "update",
+            "--quiet",
+            "--init",
+            "--recursive",
+        ]))?;
+        Ok(path)
+    }
+
+    fn release(&self, _source_id: &SourceId, path: &Utf8PathBuf) {
+        if let Err(err) = std::fs::remove_dir_all(path) {
+            tracing::warn!("couldn't remove checkout `{}`: {}", path, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::GitCheckouts;
+
+    #[test]
+    fn test_missing_git() {
+        let err = GitCheckouts::run(&mut Command::new("near-verify-rs-missing-git"))
+            .expect_err("missing executable");
+        assert_eq!(
+            err.to_string(),
+            "checkout of source code snapshot failed: `near-verify-rs-missing-git` not found in PATH"
+        );
+    }
+}
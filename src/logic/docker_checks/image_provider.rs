@@ -0,0 +1,42 @@
+use crate::logic::events::SharedObserver;
+use crate::types::report::ImageReport;
+
+/// Source of `build_environment` docker image
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ImageProvider {
+    /// Image is pulled from registry with [pull_image::check](super::pull_image::check)
+    #[default]
+    Registry,
+    /// Image is loaded from a local OCI image layout directory or tarball
+    /// with [load_image::check](super::load_image::check)
+    Archive(camino::Utf8PathBuf),
+}
+
+impl ImageProvider {
+    /// Makes `docker_image` (`build_environment`) available locally and verifies it against its digest
+    ///
+    /// `platform` (e.g. `linux/amd64`) is only used when pulling from registry,
+    /// an archive contains images for specific platforms
+    pub fn check(
+        &self,
+        docker_image: &str,
+        platform: Option<&str>,
+        observer: &SharedObserver,
+    ) -> eyre::Result<ImageReport> {
+        match self {
+            Self::Registry => super::pull_image::check_platform(docker_image, platform, observer),
+            Self::Archive(archive_path) => {
+                super::load_image::check(docker_image, archive_path, observer)
+            }
+        }
+    }
+
+    /// Local image, which build should be run in, to be passed as
+    /// [BuildOptions::image](crate::logic::nep330_build::BuildOptions::image)
+    pub fn build_image(&self, image_report: &ImageReport) -> Option<String> {
+        match self {
+            Self::Registry => None,
+            Self::Archive(_) => Some(image_report.id.clone()),
+        }
+    }
+}
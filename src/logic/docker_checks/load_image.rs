@@ -0,0 +1,168 @@
+use std::io::Write;
+use std::process::{ChildStdin, Command, Stdio};
+
+use crate::logic::events::{self, Event, Observer, SharedObserver, Stage};
+use crate::types::contract_source_metadata::docker_image_digest;
+use crate::types::report::ImageReport;
+
+mod archive;
+
+/// Loads `docker_image` (`build_environment`) from a local OCI image layout directory
+/// or a tarball, without access to registry
+///
+/// Before loading, the archive is checked to contain a manifest with the `@sha256:` digest,
+/// which `docker_image` is pinned to, and no other images, and all blobs of the archive are checked
+/// against their digests. After loading, id of the loaded image is checked to be the config digest
+/// of the pinned manifest, or of its manifest for the loaded platform.
+/// Thus the archive has to preserve registry manifests as is, e.g. one produced by
+/// `skopeo copy docker://<build_environment> oci-archive:<path>`, or by `docker save`
+/// with containerd image store. Classic `docker save` tarballs with only `manifest.json`
+/// aren't supported.
+///
+/// Returned [ImageReport::id] should be used as [BuildOptions::image](crate::logic::nep330_build::BuildOptions::image),
+/// as loaded images usually have no repo digests, which `docker_image` could be resolved with.
+pub fn check(
+    docker_image: &str,
+    archive_path: &camino::Utf8Path,
+    observer: &SharedObserver,
+) -> eyre::Result<ImageReport> {
+    let stage = Stage::LoadImage {
+        image: docker_image.to_string(),
+        archive: archive_path.to_path_buf(),
+    };
+    events::stage(observer, stage, || {
+        let image = load(docker_image, archive_path, observer)?;
+        events::emit(
+            observer,
+            Event::ImagePulled {
+                image: image.clone(),
+            },
+        );
+        Ok(image)
+    })
+}
+
+fn load(
+    docker_image: &str,
+    archive_path: &camino::Utf8Path,
+    observer: &dyn Observer,
+) -> eyre::Result<ImageReport> {
+    let digest = docker_image_digest(docker_image)?;
+    let mismatch = |err: eyre::Report| {
+        eyre::eyre!(
+            "Image archive `{}` doesn't match `{}`: {:?}",
+            archive_path,
+            docker_image,
+            err
+        )
+    };
+    let verified = archive::Layout::read(archive_path)?
+        .verify(&digest)
+        .map_err(mismatch)?;
+
+    let loaded = docker_load(archive_path, observer)?;
+
+    let mut docker_cmd = super::pull_image::docker_inspect_cmd(&loaded);
+    let output_result = docker_cmd.output();
+    let inspect =
+        super::pull_image::parse_inspect_output(&loaded, &docker_cmd, output_result, observer)?;
+    // build runs on image id, so it has to be the one of verified manifest
+    verified
+        .check_loaded(&inspect.id, &inspect.platform())
+        .map_err(mismatch)?;
+    Ok(ImageReport {
+        reference: docker_image.to_string(),
+        digest,
+        platform: inspect.platform(),
+        id: inspect.id,
+        archive: Some(archive_path.to_path_buf()),
+    })
+}
+
+const ERR_LOAD: &str = "Image archive could not be loaded!";
+
+/// Runs `docker image load` and returns a reference to the loaded image
+fn docker_load(archive_path: &camino::Utf8Path, observer: &dyn Observer) -> eyre::Result<String> {
+    let mut docker_cmd = Command::new("docker");
+    docker_cmd.args(["image", "load"]);
+    let output_result = if archive_path.is_dir() {
+        docker_cmd
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let child_result = docker_cmd.spawn();
+        let mut child =
+            super::handle_io_error(&docker_cmd, child_result, eyre::eyre!(ERR_LOAD), observer)?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        if let Err(err) = write_tar(stdin, archive_path) {
+            // `docker image load` would otherwise be left waiting for the rest of its input
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(eyre::eyre!(
+                "{} Couldn't write `{}` to `docker image load`: {}",
+                ERR_LOAD,
+                archive_path,
+                err
+            ));
+        }
+        child.wait_with_output()
+    } else {
+        docker_cmd.args(["--input", archive_path.as_str()]);
+        docker_cmd.output()
+    };
+    let output =
+        super::handle_io_error(&docker_cmd, output_result, eyre::eyre!(ERR_LOAD), observer)?;
+    if !output.status.success() {
+        super::command_failed(
+            &docker_cmd,
+            output.status,
+            Some(&output.stderr),
+            None,
+            observer,
+        );
+        return Err(eyre::eyre!(ERR_LOAD));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    loaded_image_reference(&stdout).ok_or(eyre::eyre!(
+        "{} Unexpected `docker image load` output: {}",
+        ERR_LOAD,
+        stdout
+    ))
+}
+
+/// Writes `dir` as a tarball to `stdin`, which is closed afterwards
+fn write_tar(stdin: ChildStdin, dir: &camino::Utf8Path) -> std::io::Result<()> {
+    let mut builder = tar::Builder::new(stdin);
+    builder.append_dir_all(".", dir)?;
+    builder.into_inner()?.flush()
+}
+
+/// Parses last reference from `Loaded image: <repo:tag>` or `Loaded image ID: <id>` lines
+fn loaded_image_reference(stdout: &str) -> Option<String> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            line.strip_prefix("Loaded image ID:")
+                .or_else(|| line.strip_prefix("Loaded image:"))
+        })
+        .map(|reference| reference.trim().to_string())
+        .next_back()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_loaded_image_reference() {
+        assert_eq!(
+            super::loaded_image_reference(
+                "Loaded image: sourcescan/cargo-near:0.13.4-rust-1.85.0\n"
+            ),
+            Some("sourcescan/cargo-near:0.13.4-rust-1.85.0".to_string())
+        );
+        assert_eq!(
+            super::loaded_image_reference("Loaded image ID: sha256:3a0c9fe8e4f1\n"),
+            Some("sha256:3a0c9fe8e4f1".to_string())
+        );
+        assert_eq!(super::loaded_image_reference("unexpected"), None);
+    }
+}
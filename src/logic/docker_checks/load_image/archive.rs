@@ -0,0 +1,596 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+use camino::Utf8Path;
+use serde::Deserialize;
+
+const INDEX_FILE_NAME: &str = "index.json";
+const BLOBS_DIR: &str = "blobs/sha256";
+/// Blobs up to this size are kept in memory to be parsed as indexes/manifests,
+/// larger ones (layers) are only hashed
+const MAX_KEPT_BLOB_SIZE: u64 = 4 * 1024 * 1024;
+
+const INDEX_MEDIA_TYPES: [&str; 2] = [
+    "application/vnd.oci.image.index.v1+json",
+    "application/vnd.docker.distribution.manifest.list.v2+json",
+];
+const MANIFEST_MEDIA_TYPES: [&str; 2] = [
+    "application/vnd.oci.image.manifest.v1+json",
+    "application/vnd.docker.distribution.manifest.v2+json",
+];
+const CONFIG_MEDIA_TYPES: [&str; 2] = [
+    "application/vnd.oci.image.config.v1+json",
+    "application/vnd.docker.container.image.v1+json",
+];
+const LAYER_MEDIA_TYPE_PREFIXES: [&str; 2] = [
+    "application/vnd.oci.image.layer.",
+    "application/vnd.docker.image.rootfs.",
+];
+/// Layers of attestation manifests, which `docker buildx` attaches to image indexes
+const ATTESTATION_MEDIA_TYPE: &str = "application/vnd.in-toto+json";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Descriptor {
+    #[serde(default)]
+    media_type: String,
+    digest: String,
+    platform: Option<Platform>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Platform {
+    os: String,
+    architecture: String,
+    variant: Option<String>,
+}
+
+impl Platform {
+    /// Matches `<os>/<architecture>[/<variant>]`, variant is only compared if both have one
+    fn matches(&self, platform: &str) -> bool {
+        let mut parts = platform.split('/');
+        parts.next() == Some(self.os.as_str())
+            && parts.next() == Some(self.architecture.as_str())
+            && match (self.variant.as_deref(), parts.next()) {
+                (Some(expected), Some(actual)) => expected == actual,
+                _ => true,
+            }
+    }
+}
+
+/// Image manifest, reachable from the pinned digest, which all blobs are present in archive for
+#[derive(Debug, Clone)]
+struct VerifiedManifest {
+    digest: String,
+    platform: Option<Platform>,
+    /// `sha256:<hex>` digest of image config, which is image id in docker
+    config: String,
+}
+
+/// Result of [Layout::verify]
+#[derive(Debug)]
+pub(super) struct VerifiedImage {
+    pinned_digest: String,
+    manifests: Vec<VerifiedManifest>,
+}
+
+impl VerifiedImage {
+    /// Checks that image, loaded from archive, with `image_id` and `platform` is the one of a verified manifest,
+    /// for the loaded `platform` if the pinned digest is a multi-platform index
+    pub fn check_loaded(&self, image_id: &str, platform: &str) -> eyre::Result<()> {
+        // with containerd image store image id is the digest of manifest or index itself
+        if image_id == self.pinned_digest {
+            return Ok(());
+        }
+        let manifest = match self.manifests.as_slice() {
+            [manifest] => Some(manifest),
+            manifests => manifests.iter().find(|manifest| {
+                manifest
+                    .platform
+                    .as_ref()
+                    .is_some_and(|manifest_platform| manifest_platform.matches(platform))
+            }),
+        };
+        let manifest = manifest.ok_or(eyre::eyre!(
+            "image `{}` has no manifest for platform `{}` of loaded image `{}`",
+            self.pinned_digest,
+            platform,
+            image_id
+        ))?;
+        if image_id != manifest.config && image_id != manifest.digest {
+            return Err(eyre::eyre!(
+                "loaded image `{}` isn't image `{}` (config `{}`) for platform `{}`, \
+                archive contains other images",
+                image_id,
+                manifest.digest,
+                manifest.config,
+                platform
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Index {
+    manifests: Vec<Descriptor>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    config: Descriptor,
+    layers: Vec<Descriptor>,
+}
+
+/// Content of an [OCI image layout](https://github.com/opencontainers/image-spec/blob/main/image-layout.md),
+/// either a directory or a tarball, e.g. from `skopeo copy docker://<image> oci-archive:<path>`
+/// or from `docker save` with containerd image store
+///
+/// Classic `docker save` tarballs with only `manifest.json` aren't supported, as they don't
+/// preserve registry manifests
+///
+/// Every blob's sha256 has been checked to match its file name while reading
+pub(super) struct Layout {
+    index: Vec<u8>,
+    /// `sha256:<hex>` digests of blobs, content is [Option::Some] for blobs up to [MAX_KEPT_BLOB_SIZE]
+    blobs: HashMap<String, Option<Vec<u8>>>,
+}
+
+impl Layout {
+    pub fn read(path: &Utf8Path) -> eyre::Result<Self> {
+        if path.is_dir() {
+            Self::read_dir(path)
+        } else {
+            Self::read_tar(path)
+        }
+    }
+
+    fn read_dir(path: &Utf8Path) -> eyre::Result<Self> {
+        let index = std::fs::read(path.join(INDEX_FILE_NAME))
+            .map_err(|err| eyre::eyre!("`{}` not found in `{}`: {}", INDEX_FILE_NAME, path, err))?;
+        let mut blobs = HashMap::new();
+        let blobs_dir = path.join(BLOBS_DIR);
+        for entry in blobs_dir
+            .read_dir_utf8()
+            .map_err(|err| eyre::eyre!("`{}` not found in `{}`: {}", BLOBS_DIR, path, err))?
+        {
+            let entry = entry?;
+            let file = std::fs::File::open(entry.path())?;
+            let size = file.metadata()?.len();
+            let (digest, content) = read_blob(entry.file_name(), file, size)?;
+            blobs.insert(digest, content);
+        }
+        Ok(Self { index, blobs })
+    }
+
+    fn read_tar(path: &Utf8Path) -> eyre::Result<Self> {
+        let file = std::fs::File::open(path)
+            .map_err(|err| eyre::eyre!("image archive `{}` couldn't be opened: {}", path, err))?;
+        let mut archive = tar::Archive::new(file);
+        let mut index = None;
+        let mut blobs = HashMap::new();
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let entry_path = entry.path()?.to_string_lossy().to_string();
+            let entry_path = entry_path.trim_start_matches("./");
+            if entry_path == INDEX_FILE_NAME {
+                let mut content = vec![];
+                entry.read_to_end(&mut content)?;
+                index = Some(content);
+            } else if let Some(file_name) = entry_path.strip_prefix(&format!("{}/", BLOBS_DIR)) {
+                if file_name.is_empty() {
+                    continue;
+                }
+                let file_name = file_name.to_string();
+                let size = entry.size();
+                let (digest, content) = read_blob(&file_name, entry, size)?;
+                blobs.insert(digest, content);
+            }
+        }
+        let index = index.ok_or(eyre::eyre!(
+            "`{}` not found in `{}`, only archives in OCI image layout format are supported, \
+            e.g. from `skopeo copy docker://<image> oci-archive:<path>` or from `docker save` \
+            with containerd image store; classic `docker save` tarballs with only `manifest.json` \
+            aren't supported",
+            INDEX_FILE_NAME,
+            path
+        ))?;
+        Ok(Self { index, blobs })
+    }
+
+    /// Finds a manifest or an index with `expected_digest` and checks that all of its blobs are present
+    ///
+    /// Every entry of `index.json` has to be, or to lead to, the manifest with `expected_digest`,
+    /// so that no other images are loaded from the archive.
+    /// Entries of an index, which have no blobs in the layout, are allowed, as these are usually
+    /// images for other platforms, but at least one entry should be present
+    pub fn verify(&self, expected_digest: &str) -> eyre::Result<VerifiedImage> {
+        let index: Index = serde_json::from_slice(&self.index)
+            .map_err(|err| eyre::eyre!("malformed `{}`: {}", INDEX_FILE_NAME, err))?;
+
+        let mut pinned = None;
+        for descriptor in index.manifests.iter() {
+            match self.find(descriptor, expected_digest)? {
+                Some(found) => {
+                    pinned.get_or_insert(found);
+                }
+                None => {
+                    return Err(eyre::eyre!(
+                        "entry `{}` of `{}` doesn't lead to image `{}`, \
+                        archive shouldn't contain other images",
+                        descriptor.digest,
+                        INDEX_FILE_NAME,
+                        expected_digest
+                    ))
+                }
+            }
+        }
+        let pinned = pinned.ok_or(eyre::eyre!(
+            "no manifest with digest `{}` found in image archive, \
+            archive has to preserve manifests of registry image as is",
+            expected_digest
+        ))?;
+        let mut manifests = vec![];
+        self.verify_descriptor(&pinned, &mut manifests)?;
+        Ok(VerifiedImage {
+            pinned_digest: expected_digest.to_string(),
+            manifests,
+        })
+    }
+
+    /// Finds descriptor with `expected_digest` among `descriptor` and indexes nested in it
+    fn find(
+        &self,
+        descriptor: &Descriptor,
+        expected_digest: &str,
+    ) -> eyre::Result<Option<Descriptor>> {
+        if descriptor.digest == expected_digest {
+            return Ok(Some(descriptor.clone()));
+        }
+        if INDEX_MEDIA_TYPES.contains(&descriptor.media_type.as_str()) {
+            if let Some(Some(content)) = self.blobs.get(&descriptor.digest) {
+                let nested: Index = serde_json::from_slice(content).map_err(|err| {
+                    eyre::eyre!("malformed index `{}`: {}", descriptor.digest, err)
+                })?;
+                for child in nested.manifests.iter() {
+                    if let Some(found) = self.find(child, expected_digest)? {
+                        return Ok(Some(found));
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn verify_descriptor(
+        &self,
+        descriptor: &Descriptor,
+        manifests: &mut Vec<VerifiedManifest>,
+    ) -> eyre::Result<()> {
+        let content = match self.blobs.get(&descriptor.digest) {
+            None => {
+                return Err(eyre::eyre!(
+                    "blob `{}` is missing in image archive",
+                    descriptor.digest
+                ))
+            }
+            Some(content) => content,
+        };
+        let media_type = descriptor.media_type.as_str();
+        if INDEX_MEDIA_TYPES.contains(&media_type) {
+            let content = content
+                .as_ref()
+                .ok_or(eyre::eyre!("index `{}` is too large", descriptor.digest))?;
+            let index: Index = serde_json::from_slice(content)
+                .map_err(|err| eyre::eyre!("malformed index `{}`: {}", descriptor.digest, err))?;
+            let present = index
+                .manifests
+                .iter()
+                .filter(|child| self.blobs.contains_key(&child.digest))
+                .collect::<Vec<_>>();
+            if present.is_empty() {
+                return Err(eyre::eyre!(
+                    "none of the manifests of index `{}` are present in image archive",
+                    descriptor.digest
+                ));
+            }
+            for child in present {
+                self.verify_descriptor(child, manifests)?;
+            }
+        } else if MANIFEST_MEDIA_TYPES.contains(&media_type) {
+            let content = content
+                .as_ref()
+                .ok_or(eyre::eyre!("manifest `{}` is too large", descriptor.digest))?;
+            let manifest: Manifest = serde_json::from_slice(content).map_err(|err| {
+                eyre::eyre!("malformed manifest `{}`: {}", descriptor.digest, err)
+            })?;
+            self.verify_blob(&manifest.config, &CONFIG_MEDIA_TYPES)?;
+            for layer in manifest.layers.iter() {
+                self.verify_blob(layer, &[ATTESTATION_MEDIA_TYPE])?;
+            }
+            manifests.push(VerifiedManifest {
+                digest: descriptor.digest.clone(),
+                platform: descriptor.platform.clone(),
+                config: manifest.config.digest,
+            });
+        } else {
+            return Err(eyre::eyre!(
+                "`{}` has unsupported media type `{}`, an image index or manifest is expected",
+                descriptor.digest,
+                media_type
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks that config or layer blob is present and has one of `media_types` or of layer media types
+    fn verify_blob(&self, descriptor: &Descriptor, media_types: &[&str]) -> eyre::Result<()> {
+        let media_type = descriptor.media_type.as_str();
+        let known = media_types.contains(&media_type)
+            || LAYER_MEDIA_TYPE_PREFIXES
+                .iter()
+                .any(|prefix| media_type.starts_with(prefix));
+        if !known {
+            return Err(eyre::eyre!(
+                "blob `{}` has unsupported media type `{}`",
+                descriptor.digest,
+                media_type
+            ));
+        }
+        if !self.blobs.contains_key(&descriptor.digest) {
+            return Err(eyre::eyre!(
+                "blob `{}` is missing in image archive",
+                descriptor.digest
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Hashes blob's content and checks it against `file_name`, which is the hex of its sha256
+fn read_blob<R: Read>(
+    file_name: &str,
+    mut reader: R,
+    size: u64,
+) -> eyre::Result<(String, Option<Vec<u8>>)> {
+    let mut hasher = <sha2::Sha256 as sha2::Digest>::new();
+    let content = if size <= MAX_KEPT_BLOB_SIZE {
+        let mut content = vec![];
+        reader.read_to_end(&mut content)?;
+        hasher.write_all(&content)?;
+        Some(content)
+    } else {
+        std::io::copy(&mut reader, &mut hasher)?;
+        None
+    };
+    let hash = hex::encode(sha2::Digest::finalize(hasher));
+    if hash != file_name {
+        return Err(eyre::eyre!(
+            "blob `{}/{}` in image archive has sha256 `{}`",
+            BLOBS_DIR,
+            file_name,
+            hash
+        ));
+    }
+    Ok((format!("sha256:{}", hash), content))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use camino::Utf8PathBuf;
+
+    use super::Layout;
+
+    const MANIFEST_TYPE: &str = "application/vnd.oci.image.manifest.v1+json";
+    const INDEX_TYPE: &str = "application/vnd.oci.image.index.v1+json";
+
+    fn sha256(content: &[u8]) -> String {
+        hex::encode(<sha2::Sha256 as sha2::Digest>::digest(content))
+    }
+
+    fn write_blob(dir: &Path, content: &[u8]) -> String {
+        let blobs_dir = dir.join("blobs/sha256");
+        std::fs::create_dir_all(&blobs_dir).unwrap();
+        let hash = sha256(content);
+        std::fs::write(blobs_dir.join(&hash), content).unwrap();
+        format!("sha256:{}", hash)
+    }
+
+    /// Writes blobs of a single-platform image and returns digests of its manifest and config
+    fn write_image(dir: &Path, architecture: &str, layer_type: &str) -> (String, String) {
+        let config = write_blob(
+            dir,
+            format!(r#"{{"architecture":"{}","os":"linux"}}"#, architecture).as_bytes(),
+        );
+        let layer = write_blob(dir, format!("layer {}", architecture).as_bytes());
+        let manifest = write_blob(
+            dir,
+            format!(
+                r#"{{"schemaVersion":2,"mediaType":"{}","config":{{"mediaType":"application/vnd.oci.image.config.v1+json","digest":"{}","size":1}},"layers":[{{"mediaType":"{}","digest":"{}","size":5}}]}}"#,
+                MANIFEST_TYPE, config, layer_type, layer
+            )
+            .as_bytes(),
+        );
+        (manifest, config)
+    }
+
+    /// Index content with `(media type, digest, architecture)` entries
+    fn index(entries: &[(&str, &str, Option<&str>)]) -> String {
+        let entries = entries
+            .iter()
+            .map(|(media_type, digest, architecture)| {
+                let platform = architecture
+                    .map(|architecture| {
+                        format!(
+                            r#","platform":{{"architecture":"{}","os":"linux"}}"#,
+                            architecture
+                        )
+                    })
+                    .unwrap_or_default();
+                format!(
+                    r#"{{"mediaType":"{}","digest":"{}","size":1{}}}"#,
+                    media_type, digest, platform
+                )
+            })
+            .collect::<Vec<_>>();
+        format!(
+            r#"{{"schemaVersion":2,"manifests":[{}]}}"#,
+            entries.join(",")
+        )
+    }
+
+    fn write_index(dir: &Path, entries: &[(&str, &str, Option<&str>)]) {
+        std::fs::write(dir.join("oci-layout"), r#"{"imageLayoutVersion":"1.0.0"}"#).unwrap();
+        std::fs::write(dir.join("index.json"), index(entries)).unwrap();
+    }
+
+    /// Writes a single-manifest OCI layout into `dir` and returns manifest's digest
+    fn write_layout(dir: &Path) -> String {
+        let (manifest, _) = write_image(dir, "amd64", "application/vnd.oci.image.layer.v1.tar");
+        write_index(dir, &[(MANIFEST_TYPE, &manifest, None)]);
+        manifest
+    }
+
+    fn read(dir: &Path) -> Layout {
+        Layout::read(&Utf8PathBuf::from_path_buf(dir.to_path_buf()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_verify_oci_layout_dir_and_tar() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let layout_dir = tempdir.path().join("layout");
+        let manifest = write_layout(&layout_dir);
+
+        let layout_dir = Utf8PathBuf::from_path_buf(layout_dir).unwrap();
+        Layout::read(&layout_dir)
+            .unwrap()
+            .verify(&manifest)
+            .expect("no error");
+
+        let tar_path = layout_dir.with_extension("tar");
+        let mut builder = tar::Builder::new(std::fs::File::create(&tar_path).unwrap());
+        builder.append_dir_all(".", &layout_dir).unwrap();
+        builder.finish().unwrap();
+        let layout = Layout::read(&tar_path).unwrap();
+        layout.verify(&manifest).expect("no error");
+
+        let err = layout
+            .verify("sha256:a9d8bee7b134856cc8baa142494a177f2ba9ecfededfcdd38f634e14cca8aae2")
+            .expect_err("digest mismatch");
+        assert!(format!("{:?}", err).contains("doesn't lead to image"));
+    }
+
+    #[test]
+    fn test_decline_tampered_blob() {
+        let tempdir = tempfile::tempdir().unwrap();
+        write_layout(tempdir.path());
+        let layer_path = tempdir
+            .path()
+            .join("blobs/sha256")
+            .join(sha256(b"layer amd64"));
+        std::fs::write(layer_path, b"tampered").unwrap();
+
+        let layout_dir = Utf8PathBuf::from_path_buf(tempdir.path().to_path_buf()).unwrap();
+        let Err(err) = Layout::read(&layout_dir) else {
+            panic!("Expecting an error for tampered blob");
+        };
+        assert!(format!("{:?}", err).contains("in image archive has sha256"));
+    }
+
+    #[test]
+    fn test_decline_extra_image() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let dir = tempdir.path();
+        let (pinned, pinned_config) =
+            write_image(dir, "amd64", "application/vnd.oci.image.layer.v1.tar");
+        let (extra, extra_config) =
+            write_image(dir, "arm64", "application/vnd.oci.image.layer.v1.tar");
+
+        write_index(
+            dir,
+            &[
+                (MANIFEST_TYPE, &pinned, None),
+                (MANIFEST_TYPE, &extra, None),
+            ],
+        );
+        let err = read(dir).verify(&pinned).expect_err("extra image");
+        assert!(format!("{:?}", err).contains("archive shouldn't contain other images"));
+
+        // extra image, hidden in a nested index next to the pinned one, isn't the loaded one
+        let wrapper = write_blob(
+            dir,
+            index(&[
+                (MANIFEST_TYPE, &pinned, None),
+                (MANIFEST_TYPE, &extra, None),
+            ])
+            .as_bytes(),
+        );
+        write_index(dir, &[(INDEX_TYPE, &wrapper, None)]);
+        let verified = read(dir).verify(&pinned).expect("no error");
+        verified
+            .check_loaded(&pinned_config, "linux/amd64")
+            .expect("no error");
+        let err = verified
+            .check_loaded(&extra_config, "linux/arm64")
+            .expect_err("extra image loaded");
+        assert!(err.to_string().contains("archive contains other images"));
+    }
+
+    #[test]
+    fn test_multi_platform_index() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let dir = tempdir.path();
+        let (amd64, amd64_config) =
+            write_image(dir, "amd64", "application/vnd.oci.image.layer.v1.tar+gzip");
+        let (arm64, arm64_config) = write_image(
+            dir,
+            "arm64",
+            "application/vnd.docker.image.rootfs.diff.tar.gzip",
+        );
+        let pinned = write_blob(
+            dir,
+            index(&[
+                (MANIFEST_TYPE, &amd64, Some("amd64")),
+                (MANIFEST_TYPE, &arm64, Some("arm64")),
+            ])
+            .as_bytes(),
+        );
+        write_index(dir, &[(INDEX_TYPE, &pinned, None)]);
+
+        let verified = read(dir).verify(&pinned).expect("no error");
+        verified
+            .check_loaded(&arm64_config, "linux/arm64")
+            .expect("no error");
+        verified
+            .check_loaded(&pinned, "linux/amd64")
+            .expect("containerd image store");
+        let err = verified
+            .check_loaded(&amd64_config, "linux/arm64")
+            .expect_err("config of another platform");
+        assert!(err.to_string().contains("archive contains other images"));
+        let err = verified
+            .check_loaded(&amd64_config, "linux/riscv64")
+            .expect_err("no manifest for platform");
+        assert!(err.to_string().contains("has no manifest for platform"));
+    }
+
+    #[test]
+    fn test_decline_unknown_media_type() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let dir = tempdir.path();
+        let (manifest, _) = write_image(dir, "amd64", "");
+        write_index(dir, &[(MANIFEST_TYPE, &manifest, None)]);
+        let err = read(dir)
+            .verify(&manifest)
+            .expect_err("empty layer media type");
+        assert!(format!("{:?}", err).contains("unsupported media type"));
+
+        write_index(dir, &[("", &manifest, None)]);
+        let err = read(dir)
+            .verify(&manifest)
+            .expect_err("empty manifest media type");
+        assert!(format!("{:?}", err).contains("unsupported media type"));
+    }
+}
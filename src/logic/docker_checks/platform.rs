@@ -0,0 +1,134 @@
+use crate::logic::events::{self, Event, SharedObserver};
+use crate::types::report::{DaemonReport, ImageReport, PlatformReport};
+
+/// Compares platform of docker daemon's host with platform of image
+///
+/// Returns an error if `requested` platform (`--platform`) doesn't match the platform of image.
+/// Build under emulation isn't an error, but it's recorded in [PlatformReport::emulated]
+/// and [Event::PlatformEmulated] is reported.
+pub fn check(
+    daemon: &DaemonReport,
+    image: &ImageReport,
+    requested: Option<&str>,
+    observer: &SharedObserver,
+) -> eyre::Result<PlatformReport> {
+    let host = format!(
+        "{}/{}",
+        daemon.os_type,
+        normalize_architecture(&daemon.architecture)
+    );
+    if let Some(requested) = requested {
+        if !same_platform(requested, &image.platform) {
+            return Err(eyre::eyre!(
+                "image `{}` has platform `{}`, which doesn't match requested platform `{}`",
+                image.reference,
+                image.platform,
+                requested
+            ));
+        }
+    }
+    let emulated = !same_platform(&host, &image.platform);
+    if emulated {
+        events::emit(
+            observer,
+            Event::PlatformEmulated {
+                host: host.clone(),
+                image: image.platform.clone(),
+            },
+        );
+    }
+    Ok(PlatformReport {
+        host,
+        image: image.platform.clone(),
+        requested: requested.map(ToString::to_string),
+        emulated,
+    })
+}
+
+/// Maps kernel's architecture names, reported by `docker info` and `uname -m`,
+/// to the ones used in image platforms
+pub fn normalize_architecture(architecture: &str) -> &str {
+    match architecture {
+        "x86_64" | "x86-64" | "amd64" => "amd64",
+        "aarch64" | "arm64" => "arm64",
+        "armv7l" | "armv6l" | "arm" => "arm",
+        "i386" | "i686" | "386" => "386",
+        "ppc64le" => "ppc64le",
+        "s390x" => "s390x",
+        "riscv64" => "riscv64",
+        other => other,
+    }
+}
+
+/// Compares `<os>/<architecture>` parts of platforms, variants are ignored
+fn same_platform(a: &str, b: &str) -> bool {
+    let os_arch = |platform: &str| {
+        let mut parts = platform.split('/');
+        let os = parts.next().unwrap_or_default().to_string();
+        let arch = normalize_architecture(parts.next().unwrap_or_default()).to_string();
+        (os, arch)
+    };
+    os_arch(a) == os_arch(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::logic::events::SharedObserver;
+    use crate::types::report::{DaemonReport, ImageReport};
+
+    fn daemon(architecture: &str) -> DaemonReport {
+        DaemonReport {
+            server_version: "27.3.1".into(),
+            rootless: false,
+            storage_driver: "overlay2".into(),
+            architecture: architecture.into(),
+            os_type: "linux".into(),
+        }
+    }
+
+    fn image(platform: &str) -> ImageReport {
+        ImageReport {
+            reference: "sourcescan/cargo-near:0.13.4-rust-1.85.0@sha256:a9d8bee7b134856cc8baa142494a177f2ba9ecfededfcdd38f634e14cca8aae2".into(),
+            digest: "sha256:a9d8bee7b134856cc8baa142494a177f2ba9ecfededfcdd38f634e14cca8aae2".into(),
+            id: "sha256:3a0c9fe8e4f1".into(),
+            platform: platform.into(),
+            archive: None,
+        }
+    }
+
+    #[test]
+    fn test_platform_check() {
+        let observer = SharedObserver::default();
+        let report =
+            super::check(&daemon("x86_64"), &image("linux/amd64"), None, &observer).unwrap();
+        assert_eq!(report.host, "linux/amd64");
+        assert!(!report.emulated);
+
+        let report = super::check(
+            &daemon("aarch64"),
+            &image("linux/amd64"),
+            Some("linux/amd64"),
+            &observer,
+        )
+        .unwrap();
+        assert_eq!(report.host, "linux/arm64");
+        assert!(report.emulated);
+
+        let report = super::check(
+            &daemon("aarch64"),
+            &image("linux/arm64/v8"),
+            None,
+            &observer,
+        )
+        .unwrap();
+        assert!(!report.emulated);
+
+        assert!(super::check(
+            &daemon("x86_64"),
+            &image("linux/arm64"),
+            Some("linux/amd64"),
+            &observer,
+        )
+        .is_err());
+    }
+}
@@ -1,26 +1,72 @@
-use colored::Colorize;
+use serde::Deserialize;
 
-pub fn check(docker_image: &str) -> eyre::Result<()> {
-    println!("{} {}", "docker image to be used:".green(), docker_image);
-    println!();
+use crate::logic::events::{self, Event, Observer, SharedObserver, Stage};
+use crate::types::contract_source_metadata::docker_image_digest;
+use crate::types::report::ImageReport;
 
-    let mut docker_cmd = docker_pull_cmd(docker_image);
+/// Pulls `docker_image` (`build_environment`) and verifies that the local image's repo digests
+/// contain the `@sha256:` digest, which `docker_image` is pinned to
+pub fn check(docker_image: &str, observer: &SharedObserver) -> eyre::Result<ImageReport> {
+    check_platform(docker_image, None, observer)
+}
+
+/// Same as [check], but pulls image for `platform` (`--platform`, e.g. `linux/amd64`)
+/// instead of the platform of docker daemon
+pub fn check_platform(
+    docker_image: &str,
+    platform: Option<&str>,
+    observer: &SharedObserver,
+) -> eyre::Result<ImageReport> {
+    let stage = Stage::PullImage {
+        image: docker_image.to_string(),
+    };
+    events::stage(observer, stage, || {
+        let mut docker_cmd = docker_pull_cmd(docker_image, platform);
+
+        let status_result = docker_cmd.status();
+        handle_status(docker_image, &docker_cmd, status_result, observer)?;
 
+        let mut docker_cmd = docker_inspect_cmd(docker_image);
+        let output_result = docker_cmd.output();
+        let image = parse_inspect_output(docker_image, &docker_cmd, output_result, observer)?
+            .verify(docker_image)?;
+        events::emit(
+            observer,
+            Event::ImagePulled {
+                image: image.clone(),
+            },
+        );
+        Ok(image)
+    })
+}
+
+pub(crate) fn handle_status(
+    docker_image: &str,
+    docker_cmd: &std::process::Command,
+    status_result: std::io::Result<std::process::ExitStatus>,
+    observer: &dyn Observer,
+) -> eyre::Result<()> {
     let err_report = format!("Image `{}` could not be found in registry!", docker_image);
-    let status_result = docker_cmd.status();
-    let status =
-        super::handle_io_error(&docker_cmd, status_result, eyre::eyre!(err_report.clone()))?;
+    let status = super::handle_io_error(
+        docker_cmd,
+        status_result,
+        eyre::eyre!(err_report.clone()),
+        observer,
+    )?;
     if !status.success() {
-        super::print::command_status(status, docker_cmd);
+        super::command_failed(docker_cmd, status, None, None, observer);
         return Err(eyre::eyre!(err_report));
     }
     Ok(())
 }
 
-fn docker_pull_cmd(image: &str) -> std::process::Command {
+pub(crate) fn docker_pull_cmd(image: &str, platform: Option<&str>) -> std::process::Command {
     let docker_cmd: std::process::Command = {
         let docker_args = {
             let mut docker_args = vec!["pull"];
+            if let Some(platform) = platform {
+                docker_args.extend(["--platform", platform]);
+            }
             docker_args.push(image);
             docker_args
         };
@@ -32,3 +78,137 @@ fn docker_pull_cmd(image: &str) -> std::process::Command {
     };
     docker_cmd
 }
+
+pub(crate) fn docker_inspect_cmd(image: &str) -> std::process::Command {
+    let mut docker_cmd = std::process::Command::new("docker");
+    docker_cmd.args(["image", "inspect", "--format", "{{json .}}", image]);
+    docker_cmd
+}
+
+pub(crate) fn parse_inspect_output(
+    docker_image: &str,
+    docker_cmd: &std::process::Command,
+    output_result: std::io::Result<std::process::Output>,
+    observer: &dyn Observer,
+) -> eyre::Result<ImageInspect> {
+    let err_report = format!("Image `{}` could not be inspected!", docker_image);
+    let output = super::handle_io_error(
+        docker_cmd,
+        output_result,
+        eyre::eyre!(err_report.clone()),
+        observer,
+    )?;
+    if !output.status.success() {
+        super::command_failed(
+            docker_cmd,
+            output.status,
+            Some(&output.stderr),
+            None,
+            observer,
+        );
+        return Err(eyre::eyre!(err_report));
+    }
+    serde_json::from_slice(&output.stdout).map_err(|err| {
+        eyre::eyre!(
+            "{}: unexpected `docker image inspect` output: {}",
+            err_report,
+            err
+        )
+    })
+}
+
+/// Subset of `docker image inspect` output
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct ImageInspect {
+    pub id: String,
+    #[serde(default)]
+    pub repo_digests: Vec<String>,
+    pub os: String,
+    pub architecture: String,
+    #[serde(default)]
+    pub variant: Option<String>,
+}
+
+impl ImageInspect {
+    /// `<os>/<architecture>[/<variant>]`
+    pub fn platform(&self) -> String {
+        match self.variant {
+            Some(ref variant) if !variant.is_empty() => {
+                format!("{}/{}/{}", self.os, self.architecture, variant)
+            }
+            _ => format!("{}/{}", self.os, self.architecture),
+        }
+    }
+
+    pub fn verify(self, docker_image: &str) -> eyre::Result<ImageReport> {
+        let digest = docker_image_digest(docker_image)?;
+        let digest_match = self
+            .repo_digests
+            .iter()
+            .any(|repo_digest| repo_digest.rsplit_once('@').map(|(_, d)| d) == Some(&digest));
+        if !digest_match {
+            return Err(eyre::eyre!(
+                "local image `{}` doesn't match digest `{}` of `{}`, its repo digests are: {:?}",
+                self.id,
+                digest,
+                docker_image,
+                self.repo_digests
+            ));
+        }
+        Ok(ImageReport {
+            reference: docker_image.to_string(),
+            digest,
+            platform: self.platform(),
+            id: self.id,
+            archive: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ImageInspect;
+
+    const IMAGE: &str = "sourcescan/cargo-near:0.13.4-rust-1.85.0@sha256:a9d8bee7b134856cc8baa142494a177f2ba9ecfededfcdd38f634e14cca8aae2";
+
+    #[test]
+    fn test_verify_image_digest() {
+        let inspect: ImageInspect = serde_json::from_str(
+            r#"{
+  "Id": "sha256:3a0c9fe8e4f1e5a2fd4c0eb1a7fd3f0be9d2b7d7c3f1b2aa5f1b4d3a2c1e0f9d",
+  "RepoTags": ["sourcescan/cargo-near:0.13.4-rust-1.85.0"],
+  "RepoDigests": [
+    "sourcescan/cargo-near@sha256:a9d8bee7b134856cc8baa142494a177f2ba9ecfededfcdd38f634e14cca8aae2"
+  ],
+  "Architecture": "amd64",
+  "Os": "linux"
+}"#,
+        )
+        .expect("no error");
+        let report = inspect.verify(IMAGE).expect("no error");
+        assert_eq!(report.platform, "linux/amd64");
+        assert_eq!(
+            report.digest,
+            "sha256:a9d8bee7b134856cc8baa142494a177f2ba9ecfededfcdd38f634e14cca8aae2"
+        );
+    }
+
+    #[test]
+    fn test_decline_image_digest_mismatch() {
+        let inspect: ImageInspect = serde_json::from_str(
+            r#"{
+  "Id": "sha256:3a0c9fe8e4f1e5a2fd4c0eb1a7fd3f0be9d2b7d7c3f1b2aa5f1b4d3a2c1e0f9d",
+  "RepoDigests": [
+    "sourcescan/cargo-near@sha256:722198ddb92d1b82cbfcd3a4a9f7fba6fd8715f4d0b5fb236d8725c4883f97de"
+  ],
+  "Architecture": "arm64",
+  "Variant": "v8",
+  "Os": "linux"
+}"#,
+        )
+        .expect("no error");
+        let err = inspect.verify(IMAGE).expect_err("digest mismatch");
+        assert!(format!("{:?}", err).contains("doesn't match digest"));
+    }
+}
@@ -1,29 +1,106 @@
-use colored::Colorize;
+use serde::Deserialize;
+
+use crate::logic::events::{self, Hint, Observer, SharedObserver, Stage};
+use crate::types::report::DaemonReport;
 
 const ERR_SANITY: &str = "`docker` sanity check failed!";
 
 const PERM_DENIED_STATUS: i32 = 126;
 
-pub fn check() -> eyre::Result<()> {
+pub fn check(observer: &SharedObserver) -> eyre::Result<()> {
+    events::stage(observer, Stage::DockerCheck, || {
+        let mut docker_cmd = docker_hello_world_cmd();
+        let output_result = docker_cmd.output();
+        handle_output(&docker_cmd, output_result, observer)
+    })
+}
+
+pub(crate) fn docker_hello_world_cmd() -> std::process::Command {
     let mut docker_cmd = std::process::Command::new("docker");
     docker_cmd.args(["run", "--rm", "hello-world"]);
-    let output_result = docker_cmd.output();
-    let output = super::handle_io_error(&docker_cmd, output_result, eyre::eyre!(ERR_SANITY))?;
+    docker_cmd
+}
+
+pub(crate) fn handle_output(
+    docker_cmd: &std::process::Command,
+    output_result: std::io::Result<std::process::Output>,
+    observer: &dyn Observer,
+) -> eyre::Result<()> {
+    let output =
+        super::handle_io_error(docker_cmd, output_result, eyre::eyre!(ERR_SANITY), observer)?;
 
     if !output.status.success() {
         let stderr = std::str::from_utf8(&output.stderr)?;
-        println!();
-        println!("{}", stderr.yellow());
-        if permission_denied(&output.status, stderr)? {
-            println!("{}", "Permission denied!".cyan());
-            super::print::installation_links();
-            super::print::linux_postinstall_steps();
-        } else {
-            super::print::installation_links();
+        report_failure(docker_cmd, output.status, stderr, observer)?;
+        return Err(eyre::eyre!(ERR_SANITY));
+    }
+    Ok(())
+}
+
+/// Checks that docker daemon is reachable with `docker info`, without running any containers
+/// or accessing registry
+pub fn check_daemon(observer: &SharedObserver) -> eyre::Result<DaemonReport> {
+    events::stage(observer, Stage::DockerCheck, || {
+        let mut docker_cmd = docker_info_cmd();
+        let output_result = docker_cmd.output();
+        handle_info_output(&docker_cmd, output_result, observer)
+    })
+}
+
+pub(crate) fn docker_info_cmd() -> std::process::Command {
+    let mut docker_cmd = std::process::Command::new("docker");
+    docker_cmd.args(["info", "--format", "{{json .}}"]);
+    docker_cmd
+}
+
+pub(crate) fn handle_info_output(
+    docker_cmd: &std::process::Command,
+    output_result: std::io::Result<std::process::Output>,
+    observer: &dyn Observer,
+) -> eyre::Result<DaemonReport> {
+    let output =
+        super::handle_io_error(docker_cmd, output_result, eyre::eyre!(ERR_SANITY), observer)?;
+
+    // `docker info` prints client part of info and server errors, when daemon isn't reachable
+    let info = serde_json::from_slice::<DockerInfo>(&output.stdout);
+    let server_errors = info
+        .as_ref()
+        .ok()
+        .and_then(|info| info.server_errors.clone())
+        .unwrap_or_default();
+
+    if !output.status.success() || !server_errors.is_empty() {
+        let mut stderr = std::str::from_utf8(&output.stderr)?.to_string();
+        for server_error in server_errors {
+            stderr.push_str(&server_error);
+            stderr.push('\n');
         }
-        super::print::command_status(output.status, docker_cmd);
+        report_failure(docker_cmd, output.status, &stderr, observer)?;
         return Err(eyre::eyre!(ERR_SANITY));
     }
+    let info =
+        info.map_err(|err| eyre::eyre!("{} Unexpected `docker info` output: {}", ERR_SANITY, err))?;
+    Ok(info.into())
+}
+
+fn report_failure(
+    docker_cmd: &std::process::Command,
+    status: std::process::ExitStatus,
+    stderr: &str,
+    observer: &dyn Observer,
+) -> eyre::Result<()> {
+    let hint = if permission_denied(&status, stderr)? {
+        Hint::DockerPermissionDenied
+    } else {
+        Hint::InstallDocker
+    };
+    super::command_failed(
+        docker_cmd,
+        status,
+        Some(stderr.as_bytes()),
+        Some(hint),
+        observer,
+    );
     Ok(())
 }
 
@@ -32,3 +109,90 @@ fn permission_denied(status: &std::process::ExitStatus, stderr: &str) -> eyre::R
     let stderr_match = stderr.to_lowercase().contains("permission denied");
     Ok(exit_code_match || stderr_match)
 }
+
+/// Subset of `docker info` output
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct DockerInfo {
+    #[serde(default)]
+    server_version: String,
+    #[serde(default)]
+    driver: String,
+    #[serde(default)]
+    architecture: String,
+    #[serde(default, rename = "OSType")]
+    os_type: String,
+    #[serde(default)]
+    security_options: Option<Vec<String>>,
+    #[serde(default)]
+    server_errors: Option<Vec<String>>,
+}
+
+impl From<DockerInfo> for DaemonReport {
+    fn from(info: DockerInfo) -> Self {
+        let rootless = info
+            .security_options
+            .unwrap_or_default()
+            .iter()
+            .any(|option| option.split(',').any(|part| part == "name=rootless"));
+        Self {
+            server_version: info.server_version,
+            rootless,
+            storage_driver: info.driver,
+            architecture: info.architecture,
+            os_type: info.os_type,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DockerInfo;
+    use crate::types::report::DaemonReport;
+
+    #[test]
+    fn test_daemon_report_from_docker_info() {
+        // daemon isn't reachable
+        let info: DockerInfo = serde_json::from_str(
+            r#"{"ID":"","Driver":"","OSType":"","Architecture":"","ServerVersion":"","SecurityOptions":null,"Warnings":null}"#,
+        )
+        .expect("no error");
+        assert!(!DaemonReport::from(info).rootless);
+
+        let info: DockerInfo = serde_json::from_str(
+            r#"{
+  "ServerVersion": "27.3.1",
+  "Driver": "overlay2",
+  "Architecture": "x86_64",
+  "OSType": "linux",
+  "SecurityOptions": ["name=seccomp,profile=builtin", "name=rootless", "name=cgroupns"]
+}"#,
+        )
+        .expect("no error");
+        assert_eq!(
+            DaemonReport::from(info),
+            DaemonReport {
+                server_version: "27.3.1".into(),
+                rootless: true,
+                storage_driver: "overlay2".into(),
+                architecture: "x86_64".into(),
+                os_type: "linux".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_permission_denied_server_error() {
+        let info: DockerInfo = serde_json::from_str(
+            r#"{
+  "ServerErrors": ["permission denied while trying to connect to the Docker daemon socket at unix:///var/run/docker.sock"]
+}"#,
+        )
+        .expect("no error");
+        let server_errors = info.server_errors.expect("to be some");
+        assert!(
+            super::permission_denied(&std::process::ExitStatus::default(), &server_errors[0])
+                .expect("no error")
+        );
+    }
+}
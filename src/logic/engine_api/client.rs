@@ -0,0 +1,414 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+/// Version of Engine API, which requests are made with, docker 20.10+
+const API_VERSION: &str = "v1.41";
+const DEFAULT_SOCKET: &str = "/var/run/docker.sock";
+const DOCKER_HOST: &str = "DOCKER_HOST";
+
+/// Minimal HTTP/1.1 client of Docker Engine API over unix socket
+///
+/// Every request is made on a new connection, which is closed by daemon after response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Client {
+    socket_path: PathBuf,
+}
+
+/// Error response of Docker Engine API
+///
+/// It can be distinguished from other errors with [eyre::Report::downcast_ref]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiError {
+    /// `<METHOD> <path>` of request
+    pub request: String,
+    pub status: u16,
+    /// `message` of daemon's json error response
+    pub message: String,
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "docker engine API `{}` responded with {}: {}",
+            self.request, self.status, self.message
+        )
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    message: String,
+}
+
+/// Successful response with a body, which is read lazily
+pub struct Response {
+    pub status: u16,
+    body: Box<dyn Read + Send>,
+}
+
+impl Response {
+    pub fn bytes(mut self) -> eyre::Result<Vec<u8>> {
+        let mut content = vec![];
+        self.body.read_to_end(&mut content)?;
+        Ok(content)
+    }
+
+    pub fn json<T: serde::de::DeserializeOwned>(self) -> eyre::Result<T> {
+        Ok(serde_json::from_slice(&self.bytes()?)?)
+    }
+
+    /// Body of a streaming endpoint, e.g. container logs with `follow=1`
+    pub fn into_reader(self) -> Box<dyn Read + Send> {
+        self.body
+    }
+}
+
+impl Client {
+    pub fn new(socket_path: impl Into<PathBuf>) -> Self {
+        Self {
+            socket_path: socket_path.into(),
+        }
+    }
+
+    /// Client of daemon from `DOCKER_HOST`, only `unix://` scheme is supported,
+    /// defaults to `/var/run/docker.sock`
+    pub fn from_env() -> eyre::Result<Self> {
+        match std::env::var(DOCKER_HOST) {
+            Err(_) => Ok(Self::new(DEFAULT_SOCKET)),
+            Ok(docker_host) if docker_host.is_empty() => Ok(Self::new(DEFAULT_SOCKET)),
+            Ok(docker_host) => match docker_host.strip_prefix("unix://") {
+                Some(socket_path) => Ok(Self::new(socket_path)),
+                None => Err(eyre::eyre!(
+                    "`{}={}` isn't supported by engine API backend, only `unix://` sockets are",
+                    DOCKER_HOST,
+                    docker_host
+                )),
+            },
+        }
+    }
+
+    pub fn socket_path(&self) -> &std::path::Path {
+        &self.socket_path
+    }
+
+    pub fn get(&self, path: &str) -> eyre::Result<Response> {
+        self.request("GET", path, None)
+    }
+
+    pub fn post(&self, path: &str, body: Option<&serde_json::Value>) -> eyre::Result<Response> {
+        self.request("POST", path, body)
+    }
+
+    pub fn delete(&self, path: &str) -> eyre::Result<Response> {
+        self.request("DELETE", path, None)
+    }
+
+    /// Sends a request to `/<API_VERSION><path>`, `path` should include url-encoded query
+    ///
+    /// Responses with status 400 and above are returned as [ApiError]
+    pub fn request(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<&serde_json::Value>,
+    ) -> eyre::Result<Response> {
+        let mut stream = UnixStream::connect(&self.socket_path).map_err(|err| {
+            eyre::eyre!(
+                "couldn't connect to docker daemon at `{}`: {}",
+                self.socket_path.display(),
+                err
+            )
+        })?;
+        let body = body.map(serde_json::to_vec).transpose()?;
+
+        let mut request = format!(
+            "{} /{}{} HTTP/1.1\r\nHost: docker\r\nConnection: close\r\n",
+            method, API_VERSION, path
+        );
+        if let Some(ref body) = body {
+            request.push_str("Content-Type: application/json\r\n");
+            request.push_str(&format!("Content-Length: {}\r\n", body.len()));
+        } else if method != "GET" {
+            request.push_str("Content-Length: 0\r\n");
+        }
+        request.push_str("\r\n");
+        tracing::debug!("docker engine API request: {} {}", method, path);
+        stream.write_all(request.as_bytes())?;
+        if let Some(ref body) = body {
+            stream.write_all(body)?;
+        }
+        stream.flush()?;
+
+        let response = read_response(BufReader::new(stream))?;
+        if response.status >= 400 {
+            let status = response.status;
+            let content = response.bytes()?;
+            let message = serde_json::from_slice::<ErrorBody>(&content)
+                .map(|body| body.message)
+                .unwrap_or_else(|_| String::from_utf8_lossy(&content).trim().to_string());
+            return Err(eyre::Report::new(ApiError {
+                request: format!("{} {}", method, path),
+                status,
+                message,
+            }));
+        }
+        Ok(response)
+    }
+}
+
+/// `key=value&...` query with url-encoded values
+pub(crate) fn query(pairs: &[(&str, &str)]) -> String {
+    url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(pairs)
+        .finish()
+}
+
+fn read_response<R: BufRead + Send + 'static>(mut reader: R) -> eyre::Result<Response> {
+    let status_line = read_line(&mut reader)?;
+    let status = status_line
+        .split(' ')
+        .nth(1)
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or(eyre::eyre!(
+            "malformed docker engine API response: `{}`",
+            status_line
+        ))?;
+
+    let mut content_length = None;
+    let mut chunked = false;
+    loop {
+        let header = read_line(&mut reader)?;
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = Some(value.parse::<u64>()?);
+        } else if name.eq_ignore_ascii_case("transfer-encoding") {
+            chunked = value.eq_ignore_ascii_case("chunked");
+        }
+    }
+
+    let body: Box<dyn Read + Send> = if chunked {
+        Box::new(Chunked {
+            reader,
+            remaining: 0,
+            done: false,
+        })
+    } else if let Some(content_length) = content_length {
+        Box::new(reader.take(content_length))
+    } else {
+        Box::new(reader)
+    };
+    Ok(Response { status, body })
+}
+
+/// Line without trailing `\r\n`
+fn read_line<R: BufRead>(reader: &mut R) -> eyre::Result<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(eyre::eyre!(
+            "docker engine API connection closed unexpectedly"
+        ));
+    }
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Decoder of `Transfer-Encoding: chunked` body
+struct Chunked<R> {
+    reader: R,
+    /// Bytes left in current chunk
+    remaining: u64,
+    done: bool,
+}
+
+impl<R: BufRead> Read for Chunked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+        if self.remaining == 0 {
+            let size_line = read_line(&mut self.reader).map_err(std::io::Error::other)?;
+            let size = size_line.split(';').next().unwrap_or_default().trim();
+            let size = u64::from_str_radix(size, 16).map_err(|err| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("malformed chunk size `{}`: {}", size_line, err),
+                )
+            })?;
+            if size == 0 {
+                // trailer headers, if any, end with an empty line
+                while !read_line(&mut self.reader)
+                    .map_err(std::io::Error::other)?
+                    .is_empty()
+                {}
+                self.done = true;
+                return Ok(0);
+            }
+            self.remaining = size;
+        }
+        let max = buf.len().min(self.remaining as usize);
+        let read = self.reader.read(&mut buf[..max])?;
+        if read == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        self.remaining -= read as u64;
+        if self.remaining == 0 {
+            // `\r\n` after chunk's data
+            read_line(&mut self.reader).map_err(std::io::Error::other)?;
+        }
+        Ok(read)
+    }
+}
+
+/// Mock docker daemon for tests, listening on a unix socket in a temporary directory
+#[cfg(test)]
+pub(crate) mod mock {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::os::unix::net::UnixListener;
+    use std::sync::{Arc, Mutex};
+
+    pub struct Route {
+        pub method: &'static str,
+        /// Prefix of request's path, without API version
+        pub path: &'static str,
+        pub status: u16,
+        pub body: Vec<u8>,
+    }
+
+    impl Route {
+        pub fn new(method: &'static str, path: &'static str, status: u16, body: &[u8]) -> Self {
+            Self {
+                method,
+                path,
+                status,
+                body: body.to_vec(),
+            }
+        }
+    }
+
+    pub struct Daemon {
+        _dir: tempfile::TempDir,
+        pub client: super::Client,
+        /// `<METHOD> <path>` of received requests
+        pub requests: Arc<Mutex<Vec<String>>>,
+    }
+
+    /// Serves `routes` in a background thread, first matching route is used for each request,
+    /// bodies are sent with chunked encoding
+    pub fn start(routes: Vec<Route>) -> Daemon {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("docker.sock");
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        let requests = Arc::new(Mutex::new(vec![]));
+        let received = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    break;
+                };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut content_length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some(value) = header.to_lowercase().strip_prefix("content-length:") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+
+                let mut parts = request_line.split(' ');
+                let method = parts.next().unwrap().to_string();
+                let path = parts
+                    .next()
+                    .unwrap()
+                    .trim_start_matches("/v1.41")
+                    .to_string();
+                received
+                    .lock()
+                    .unwrap()
+                    .push(format!("{} {}", method, path));
+
+                let route = routes
+                    .iter()
+                    .find(|route| route.method == method && path.starts_with(route.path));
+                let (status, body) = match route {
+                    Some(route) => (route.status, route.body.clone()),
+                    None => (404, br#"{"message":"page not found"}"#.to_vec()),
+                };
+                let mut response = format!(
+                    "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n",
+                    status
+                )
+                .into_bytes();
+                // split into 2 chunks to exercise decoder
+                for chunk in body.chunks(body.len().div_ceil(2).max(1)) {
+                    response.extend(format!("{:x}\r\n", chunk.len()).as_bytes());
+                    response.extend(chunk);
+                    response.extend(b"\r\n");
+                }
+                response.extend(b"0\r\n\r\n");
+                let _ = stream.write_all(&response);
+            }
+        });
+        Daemon {
+            client: super::Client::new(socket_path),
+            _dir: dir,
+            requests,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::{self, Route};
+    use super::ApiError;
+
+    #[test]
+    fn test_chunked_response_and_api_error() {
+        let daemon = mock::start(vec![
+            Route::new("GET", "/_ping", 200, b"OK"),
+            Route::new(
+                "POST",
+                "/containers/create",
+                404,
+                br#"{"message":"No such image: sourcescan/cargo-near:0.13.4-rust-1.85.0"}"#,
+            ),
+        ]);
+        let body = daemon.client.get("/_ping").unwrap().bytes().unwrap();
+        assert_eq!(body, b"OK");
+
+        let err = daemon
+            .client
+            .post("/containers/create?name=test", Some(&serde_json::json!({})))
+            .err()
+            .expect("not found");
+        let err = err.downcast_ref::<ApiError>().expect("an api error");
+        assert_eq!(err.status, 404);
+        assert!(err.message.starts_with("No such image"));
+    }
+
+    #[test]
+    fn test_query_encoding() {
+        assert_eq!(
+            super::query(&[("fromImage", "sourcescan/cargo-near:0.13.4@sha256:a9d8")]),
+            "fromImage=sourcescan%2Fcargo-near%3A0.13.4%40sha256%3Aa9d8"
+        );
+    }
+}
@@ -0,0 +1,125 @@
+use std::io::{BufRead, BufReader};
+
+use serde::Deserialize;
+
+use crate::logic::docker_checks::pull_image::ImageInspect;
+use crate::logic::events::{self, Event, Observer, SharedObserver, Stage};
+use crate::types::report::ImageReport;
+
+use super::super::{client, Client};
+
+/// A line of `/images/create` progress stream
+#[derive(Debug, Deserialize)]
+struct PullProgress {
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// Engine API variant of [pull_image::check_platform](crate::logic::docker_checks::pull_image::check_platform)
+pub fn check_platform(
+    client: &Client,
+    docker_image: &str,
+    platform: Option<&str>,
+    observer: &SharedObserver,
+) -> eyre::Result<ImageReport> {
+    let stage = Stage::PullImage {
+        image: docker_image.to_string(),
+    };
+    events::stage(observer, stage, || {
+        pull(client, docker_image, platform, observer)?;
+
+        let inspect: ImageInspect = client
+            .get(&format!("/images/{}/json", docker_image))
+            .and_then(|response| response.json())
+            .map_err(|err| {
+                eyre::eyre!("Image `{}` could not be inspected! {:#}", docker_image, err)
+            })?;
+        let image = inspect.verify(docker_image)?;
+        events::emit(
+            observer,
+            Event::ImagePulled {
+                image: image.clone(),
+            },
+        );
+        Ok(image)
+    })
+}
+
+/// Pulls image with `/images/create`, waiting for the end of its progress stream
+pub(crate) fn pull(
+    client: &Client,
+    docker_image: &str,
+    platform: Option<&str>,
+    observer: &dyn Observer,
+) -> eyre::Result<()> {
+    let err_report = format!("Image `{}` could not be found in registry!", docker_image);
+    let mut query = vec![("fromImage", docker_image)];
+    if let Some(platform) = platform {
+        query.push(("platform", platform));
+    }
+    let response = client
+        .post(&format!("/images/create?{}", client::query(&query)), None)
+        .map_err(|err| eyre::eyre!("{} {:#}", err_report, err))?;
+
+    // errors of pull are reported inside of progress stream, with status 200
+    for line in BufReader::new(response.into_reader()).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        tracing::debug!("pull progress: {}", line);
+        if let Ok(PullProgress { error: Some(error) }) = serde_json::from_str(&line) {
+            events::emit(observer, Event::Warning { message: error });
+            return Err(eyre::eyre!(err_report));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::logic::engine_api::client::mock::{self, Route};
+
+    const IMAGE: &str = "sourcescan/cargo-near:0.13.4-rust-1.85.0@sha256:a9d8bee7b134856cc8baa142494a177f2ba9ecfededfcdd38f634e14cca8aae2";
+
+    #[test]
+    fn test_pull_and_verify() {
+        let daemon = mock::start(vec![
+            Route::new(
+                "POST",
+                "/images/create",
+                200,
+                b"{\"status\":\"Pulling from sourcescan/cargo-near\"}\n{\"status\":\"Digest: sha256:a9d8\"}\n",
+            ),
+            Route::new(
+                "GET",
+                "/images/",
+                200,
+                br#"{"Id":"sha256:3a0c","RepoDigests":["sourcescan/cargo-near@sha256:a9d8bee7b134856cc8baa142494a177f2ba9ecfededfcdd38f634e14cca8aae2"],"Architecture":"amd64","Os":"linux"}"#,
+            ),
+        ]);
+        let report = super::check_platform(
+            &daemon.client,
+            IMAGE,
+            Some("linux/amd64"),
+            &Default::default(),
+        )
+        .expect("no error");
+        assert_eq!(report.id, "sha256:3a0c");
+        assert_eq!(report.platform, "linux/amd64");
+    }
+
+    #[test]
+    fn test_pull_error_in_progress_stream() {
+        let daemon = mock::start(vec![Route::new(
+            "POST",
+            "/images/create",
+            200,
+            b"{\"status\":\"Pulling\"}\n{\"errorDetail\":{\"message\":\"manifest unknown\"},\"error\":\"manifest unknown\"}\n",
+        )]);
+        let err = super::check_platform(&daemon.client, IMAGE, None, &Default::default())
+            .expect_err("pull failed");
+        assert!(format!("{:?}", err).contains("could not be found in registry"));
+        assert_eq!(daemon.requests.lock().unwrap().len(), 1);
+    }
+}
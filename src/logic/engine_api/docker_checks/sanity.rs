@@ -0,0 +1,59 @@
+use crate::logic::docker_checks::sanity::DockerInfo;
+use crate::logic::events::{self, SharedObserver, Stage};
+use crate::types::report::DaemonReport;
+
+use super::super::Client;
+
+const ERR_SANITY: &str = "docker engine API sanity check failed!";
+
+/// Engine API variant of [sanity::check](crate::logic::docker_checks::sanity::check),
+/// checks that daemon responds to `/_ping`
+pub fn check(client: &Client, observer: &SharedObserver) -> eyre::Result<()> {
+    events::stage(observer, Stage::DockerCheck, || {
+        let body = client
+            .get("/_ping")
+            .and_then(|response| response.bytes())
+            .map_err(|err| eyre::eyre!("{} {:#}", ERR_SANITY, err))?;
+        if body != b"OK" {
+            return Err(eyre::eyre!(
+                "{} Unexpected `/_ping` response: {}",
+                ERR_SANITY,
+                String::from_utf8_lossy(&body)
+            ));
+        }
+        Ok(())
+    })
+}
+
+/// Engine API variant of [sanity::check_daemon](crate::logic::docker_checks::sanity::check_daemon)
+pub fn check_daemon(client: &Client, observer: &SharedObserver) -> eyre::Result<DaemonReport> {
+    events::stage(observer, Stage::DockerCheck, || {
+        let info: DockerInfo = client
+            .get("/info")
+            .and_then(|response| response.json())
+            .map_err(|err| eyre::eyre!("{} {:#}", ERR_SANITY, err))?;
+        Ok(info.into())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::logic::engine_api::client::mock::{self, Route};
+
+    #[test]
+    fn test_check_daemon() {
+        let daemon = mock::start(vec![Route::new(
+            "GET",
+            "/info",
+            200,
+            br#"{"ServerVersion":"27.3.1","Driver":"overlay2","Architecture":"aarch64","OSType":"linux","SecurityOptions":["name=seccomp,profile=builtin"]}"#,
+        )]);
+        let report = super::check_daemon(&daemon.client, &Default::default()).expect("no error");
+        assert_eq!(report.architecture, "aarch64");
+        assert!(!report.rootless);
+
+        let err =
+            super::check(&daemon.client, &Default::default()).expect_err("`/_ping` isn't served");
+        assert!(format!("{:?}", err).contains("page not found"));
+    }
+}
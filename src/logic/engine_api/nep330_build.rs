@@ -0,0 +1,577 @@
+use std::io::{Read, Write};
+use std::os::unix::process::ExitStatusExt;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::JoinHandle;
+
+use serde::Deserialize;
+
+use crate::logic::events::{self, Event, Observer, SharedObserver};
+use crate::logic::nep330_build::{
+    self, capture, BuildOptions, CaptureOptions, ContainerSpec, ExitedRun, OutputMode, PullPolicy,
+    TimeoutError, ERR_REPRODUCIBLE,
+};
+use crate::types::contract_source_metadata::ContractSourceMetadata;
+use crate::types::report::{BuildLog, BuildReport, Stream};
+
+use super::docker_checks::pull_image;
+use super::{client, ApiError, Client};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CreateResponse {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct WaitResponse {
+    status_code: i64,
+    #[serde(default)]
+    error: Option<WaitError>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct WaitError {
+    #[serde(default)]
+    message: String,
+}
+
+/// Engine API variant of [nep330_build::run]
+///
+/// Build container is created, started, waited for and removed with requests to docker daemon,
+/// its output is read from `/containers/{id}/logs` stream.
+/// [BuildReport::docker_command] is the equivalent `docker run` invocation.
+///
+/// [BuildOptions::unsafe_raw_docker_args] aren't supported, as these are `docker` CLI arguments,
+/// and neither is [BuildOptions::tty]
+pub fn run(
+    client: &Client,
+    contract_source_metadata: ContractSourceMetadata,
+    contract_source_workdir: camino::Utf8PathBuf,
+    build_options: BuildOptions,
+) -> eyre::Result<BuildReport> {
+    let spec = ContainerSpec::compute(
+        &contract_source_metadata,
+        &contract_source_workdir,
+        &build_options,
+    )?;
+    let stage = spec.stage();
+    let _span = stage.span().entered();
+    let observer = build_options.observer.clone();
+    events::emit(
+        &observer,
+        Event::StageStarted {
+            stage: stage.clone(),
+        },
+    );
+    let result = run_container(
+        client,
+        contract_source_metadata,
+        contract_source_workdir,
+        build_options,
+        spec,
+    );
+    events::emit(
+        &observer,
+        Event::StageFinished {
+            stage,
+            success: result.is_ok(),
+        },
+    );
+    result
+}
+
+fn run_container(
+    client: &Client,
+    contract_source_metadata: ContractSourceMetadata,
+    contract_source_workdir: camino::Utf8PathBuf,
+    build_options: BuildOptions,
+    spec: ContainerSpec,
+) -> eyre::Result<BuildReport> {
+    if !spec.unsafe_raw_docker_args.is_empty() {
+        return Err(eyre::eyre!(
+            "raw docker args {:?} aren't supported by engine API backend",
+            spec.unsafe_raw_docker_args
+        ));
+    }
+    if spec.tty {
+        return Err(eyre::eyre!("tty isn't supported by engine API backend"));
+    }
+    let docker_command = std::iter::once("docker".to_string())
+        .chain(std::iter::once("run".to_string()))
+        .chain(spec.docker_run_args())
+        .collect::<Vec<_>>();
+
+    let mut query = vec![("name", spec.name.as_str())];
+    if let Some(ref platform) = spec.platform {
+        query.push(("platform", platform));
+    }
+    let create_path = format!("/containers/create?{}", client::query(&query));
+    let create_body = create_body(&spec)?;
+    if build_options.pull_policy == PullPolicy::Always {
+        pull_image::pull(
+            client,
+            &spec.image,
+            spec.platform.as_deref(),
+            &build_options.observer,
+        )?;
+    }
+    let created = match client.post(&create_path, Some(&create_body)) {
+        Err(err)
+            if build_options.pull_policy == PullPolicy::Missing
+                && err.downcast_ref::<ApiError>().map(|err| err.status) == Some(404) =>
+        {
+            pull_image::pull(
+                client,
+                &spec.image,
+                spec.platform.as_deref(),
+                &build_options.observer,
+            )?;
+            client.post(&create_path, Some(&create_body))
+        }
+        created => created,
+    };
+    let created: CreateResponse = created
+        .and_then(|response| response.json())
+        .map_err(|err| eyre::eyre!("{} {:#}", ERR_REPRODUCIBLE, err))?;
+    let guard = ContainerGuard {
+        client: client.clone(),
+        id: created.id,
+    };
+
+    client
+        .post(&format!("/containers/{}/start", guard.id), None)
+        .map_err(|err| eyre::eyre!("{} {:#}", ERR_REPRODUCIBLE, err))?;
+    events::emit(
+        &build_options.observer,
+        Event::ContainerStarted {
+            container_name: spec.name.clone(),
+            docker_command: docker_command.clone(),
+        },
+    );
+
+    let logs = client
+        .get(&format!(
+            "/containers/{}/logs?follow=1&stdout=1&stderr=1",
+            guard.id
+        ))?
+        .into_reader();
+    let logs = Logs::start(
+        logs,
+        &spec.name,
+        build_options.output,
+        &build_options.observer,
+    );
+
+    let (sender, receiver) = mpsc::channel();
+    {
+        let client = client.clone();
+        let path = format!("/containers/{}/wait", guard.id);
+        std::thread::spawn(move || {
+            let result = client
+                .post(&path, None)
+                .and_then(|response| response.json::<WaitResponse>());
+            let _ = sender.send(result);
+        });
+    }
+    let wait_result = match build_options.limits.timeout {
+        None => receiver.recv()?,
+        Some(timeout) => match receiver.recv_timeout(timeout) {
+            Ok(wait_result) => wait_result,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                tracing::warn!(
+                    "build container `{}` exceeded timeout of {:?}, killing it",
+                    spec.name,
+                    timeout
+                );
+                guard.kill();
+                return Err(eyre::Report::new(TimeoutError {
+                    container_name: spec.name,
+                    timeout,
+                    log: logs.finish(),
+                }));
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                return Err(eyre::eyre!("waiting for build container failed"))
+            }
+        },
+    };
+    let wait = wait_result.map_err(|err| eyre::eyre!("{} {:#}", ERR_REPRODUCIBLE, err))?;
+    if let Some(WaitError { message }) = wait.error {
+        if !message.is_empty() {
+            return Err(eyre::eyre!("{} {}", ERR_REPRODUCIBLE, message));
+        }
+    }
+    let log = logs.finish();
+    drop(guard);
+
+    // wait status encoding, exit code in the second byte
+    let status = std::process::ExitStatus::from_raw(((wait.status_code & 0xff) as i32) << 8);
+    nep330_build::handle_docker_run_status(
+        contract_source_metadata,
+        contract_source_workdir,
+        ExitedRun {
+            status,
+            docker_command,
+            container_name: spec.name,
+            log,
+            cargo_cache: spec.cargo_cache,
+        },
+        &build_options.observer,
+    )
+}
+
+fn create_body(spec: &ContainerSpec) -> eyre::Result<serde_json::Value> {
+    Ok(serde_json::json!({
+        "Image": spec.image,
+        "Cmd": spec.cmd,
+        "User": spec.user.clone().unwrap_or_default(),
+        "WorkingDir": spec.workdir,
+        "Env": spec.env,
+        "Tty": false,
+        "AttachStdout": true,
+        "AttachStderr": true,
+        "Labels": spec.labels,
+        "HostConfig": host_config(spec)?,
+    }))
+}
+
+fn host_config(spec: &ContainerSpec) -> eyre::Result<serde_json::Value> {
+    let binds = std::iter::once(&spec.volume)
+        .chain(spec.mounts.iter())
+        .chain(
+            spec.cargo_cache
+                .iter()
+                .flat_map(|cache| cache.volumes.iter()),
+        )
+        .collect::<Vec<_>>();
+    let mut host_config = serde_json::json!({ "Binds": binds });
+    if let Some(ref network) = spec.network {
+        host_config["NetworkMode"] = network.clone().into();
+    }
+    if let Some(ref userns) = spec.userns {
+        host_config["UsernsMode"] = userns.clone().into();
+    }
+    let limits = &spec.limits;
+    if let Some(ref cpus) = limits.cpus {
+        let cpus: f64 = cpus
+            .parse()
+            .map_err(|err| eyre::eyre!("invalid cpus limit `{}`: {}", cpus, err))?;
+        host_config["NanoCpus"] = ((cpus * 1e9) as i64).into();
+    }
+    if let Some(ref memory) = limits.memory {
+        host_config["Memory"] = parse_bytes(memory)?.into();
+    }
+    if let Some(pids) = limits.pids {
+        host_config["PidsLimit"] = pids.into();
+    }
+    if let Some(ref disk) = limits.disk {
+        host_config["StorageOpt"] = serde_json::json!({ "size": disk });
+    }
+    Ok(host_config)
+}
+
+/// Size with an optional `b`, `k`, `m` or `g` suffix, as accepted by `docker run --memory`
+fn parse_bytes(size: &str) -> eyre::Result<u64> {
+    let lowercase = size.trim().to_lowercase();
+    let (number, multiplier) = match lowercase.chars().last() {
+        Some('b') => (&lowercase[..lowercase.len() - 1], 1),
+        Some('k') => (&lowercase[..lowercase.len() - 1], 1 << 10),
+        Some('m') => (&lowercase[..lowercase.len() - 1], 1 << 20),
+        Some('g') => (&lowercase[..lowercase.len() - 1], 1 << 30),
+        _ => (lowercase.as_str(), 1),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|err| eyre::eyre!("invalid memory limit `{}`: {}", size, err))?;
+    Ok(number * multiplier)
+}
+
+/// Removes build container when dropped
+struct ContainerGuard {
+    client: Client,
+    id: String,
+}
+
+impl ContainerGuard {
+    fn kill(&self) {
+        if let Err(err) = self
+            .client
+            .post(&format!("/containers/{}/kill", self.id), None)
+        {
+            tracing::warn!("killing build container `{}` failed: {:#}", self.id, err);
+        }
+    }
+}
+
+impl Drop for ContainerGuard {
+    fn drop(&mut self) {
+        if let Err(err) = self
+            .client
+            .delete(&format!("/containers/{}?force=1", self.id))
+        {
+            tracing::warn!("removing build container `{}` failed: {:#}", self.id, err);
+        }
+    }
+}
+
+/// Reader of multiplexed `/containers/{id}/logs` stream of a container without tty
+///
+/// Output is forwarded to stdout/stderr of current process with [OutputMode::Inherit],
+/// and collected into [BuildLog] with [OutputMode::Capture]
+struct Logs {
+    log: Option<Arc<Mutex<BuildLog>>>,
+    reader: JoinHandle<()>,
+}
+
+impl Logs {
+    fn start(
+        stream: Box<dyn Read + Send>,
+        container_name: &str,
+        output: OutputMode,
+        observer: &SharedObserver,
+    ) -> Self {
+        let (log, options) = match output {
+            OutputMode::Inherit => (None, None),
+            OutputMode::Capture(options) => (
+                Some(Arc::new(Mutex::new(BuildLog::default()))),
+                Some(options),
+            ),
+        };
+        let reader = {
+            let log = log.clone();
+            let container_name = container_name.to_string();
+            let observer = observer.clone();
+            std::thread::spawn(move || {
+                let target = log.as_ref().zip(options.as_ref());
+                if let Err(err) = demultiplex(stream, &container_name, target, &observer) {
+                    tracing::warn!("error reading build container logs: {:?}", err);
+                }
+            })
+        };
+        Self { log, reader }
+    }
+
+    /// Waits for logs stream to be closed, which happens when container stops
+    fn finish(self) -> Option<BuildLog> {
+        if self.reader.join().is_err() {
+            tracing::warn!("build container logs reader thread panicked");
+        }
+        let log = self.log?;
+        let mut log = log.lock().expect("reader thread has finished");
+        Some(std::mem::take(&mut *log))
+    }
+}
+
+/// Splits stream into frames with 8-byte headers: stream type, 3 zero bytes, big-endian u32 size
+fn demultiplex(
+    mut stream: Box<dyn Read + Send>,
+    container_name: &str,
+    capture: Option<(&Arc<Mutex<BuildLog>>, &CaptureOptions)>,
+    observer: &dyn Observer,
+) -> eyre::Result<()> {
+    let max_bytes = capture.map_or(0, |(_, options)| options.max_bytes);
+    let mut stdout_lines = capture::Lines::new(max_bytes);
+    let mut stderr_lines = capture::Lines::new(max_bytes);
+    let mut header = [0u8; 8];
+    let mut chunk = [0u8; capture::CHUNK_SIZE];
+    loop {
+        match stream.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err.into()),
+        }
+        let size = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+        let stream_type = if header[0] == 2 {
+            Stream::Stderr
+        } else {
+            Stream::Stdout
+        };
+        // frame size comes from the daemon, so its payload is read in chunks
+        let mut payload = (&mut stream).take(size.into());
+        loop {
+            let read = match payload.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            };
+            let chunk = &chunk[..read];
+            match capture {
+                None => match stream_type {
+                    Stream::Stdout => std::io::stdout().write_all(chunk)?,
+                    Stream::Stderr => std::io::stderr().write_all(chunk)?,
+                },
+                Some((log, options)) => {
+                    let lines = match stream_type {
+                        Stream::Stdout => &mut stdout_lines,
+                        Stream::Stderr => &mut stderr_lines,
+                    };
+                    lines.push(chunk, |line, truncated| {
+                        capture::record_line(
+                            line,
+                            truncated,
+                            stream_type,
+                            container_name,
+                            log,
+                            options,
+                            observer,
+                        )
+                    });
+                }
+            }
+        }
+        if payload.limit() > 0 {
+            return Err(eyre::eyre!("logs stream ended in the middle of a frame"));
+        }
+    }
+    if let Some((log, options)) = capture {
+        for (lines, stream_type) in [
+            (stdout_lines, Stream::Stdout),
+            (stderr_lines, Stream::Stderr),
+        ] {
+            lines.finish(|line, truncated| {
+                capture::record_line(
+                    line,
+                    truncated,
+                    stream_type,
+                    container_name,
+                    log,
+                    options,
+                    observer,
+                )
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::logic::engine_api::client::mock::{self, Route};
+    use crate::logic::events::{Event, Observer};
+    use crate::logic::nep330_build::{BuildFailedError, BuildOptions, CaptureOptions, OutputMode};
+    use crate::types::contract_source_metadata::ContractSourceMetadata;
+    use crate::types::report::Stream;
+
+    fn frame(stream_type: u8, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![stream_type, 0, 0, 0];
+        frame.extend((payload.len() as u32).to_be_bytes());
+        frame.extend(payload);
+        frame
+    }
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<Event>>);
+
+    impl Observer for Recorder {
+        fn event(&self, event: &Event) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    #[test]
+    fn test_parse_bytes() {
+        assert_eq!(super::parse_bytes("512").unwrap(), 512);
+        assert_eq!(super::parse_bytes("4g").unwrap(), 4 << 30);
+        assert_eq!(super::parse_bytes("256M").unwrap(), 256 << 20);
+        assert!(super::parse_bytes("lots").is_err());
+    }
+
+    #[test]
+    fn test_failed_build_is_captured_and_removed() {
+        let mut logs = frame(1, b"   Compiling contract v0.1.0\n  err");
+        logs.extend(frame(2, b"error[E0425]: cannot find value\n"));
+        logs.extend(frame(1, b"or: could not compile\n"));
+        let daemon = mock::start(vec![
+            Route::new(
+                "POST",
+                "/containers/create",
+                201,
+                br#"{"Id":"c0ffee","Warnings":[]}"#,
+            ),
+            Route::new("POST", "/containers/c0ffee/start", 204, b""),
+            Route::new("GET", "/containers/c0ffee/logs", 200, &logs),
+            Route::new(
+                "POST",
+                "/containers/c0ffee/wait",
+                200,
+                br#"{"StatusCode":101,"Error":null}"#,
+            ),
+            Route::new("DELETE", "/containers/c0ffee", 204, b""),
+        ]);
+
+        let meta: ContractSourceMetadata = serde_json::from_str(
+            r#"{
+  "build_info": {
+    "build_command": ["cargo", "near", "build", "non-reproducible-wasm", "--locked"],
+    "build_environment": "sourcescan/cargo-near:0.13.4-rust-1.85.0@sha256:a9d8bee7b134856cc8baa142494a177f2ba9ecfededfcdd38f634e14cca8aae2",
+    "contract_path": "",
+    "source_code_snapshot": "git+https://github.com/dj8yfo/verify_contracts_collection?rev=e3303f0cf8761b99c84ec40a4b7e2a3b3e3b5e3c"
+  },
+  "link": null,
+  "standards": [],
+  "version": "0.1.0"
+}"#,
+        )
+        .unwrap();
+        let workdir = tempfile::tempdir().unwrap();
+        let workdir = camino::Utf8PathBuf::from_path_buf(workdir.path().to_path_buf()).unwrap();
+        let recorder = Arc::new(Recorder::default());
+
+        let err = super::run(
+            &daemon.client,
+            meta,
+            workdir,
+            BuildOptions {
+                output: OutputMode::Capture(CaptureOptions::default()),
+                observer: (recorder.clone() as Arc<dyn Observer>).into(),
+                ..Default::default()
+            },
+        )
+        .expect_err("build failed");
+        let err = err
+            .downcast_ref::<BuildFailedError>()
+            .expect("a build failure");
+        assert_eq!(err.exit_code, Some(101));
+        let log = err.log.as_ref().expect("log is captured");
+        let lines = log
+            .lines
+            .iter()
+            .map(|line| (line.stream, line.line.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec![
+                (Stream::Stdout, "   Compiling contract v0.1.0"),
+                (Stream::Stderr, "error[E0425]: cannot find value"),
+                (Stream::Stdout, "  error: could not compile"),
+            ]
+        );
+
+        // events of the build go to its own observer
+        let events = recorder.0.lock().unwrap();
+        assert!(matches!(events[0], Event::StageStarted { .. }));
+        assert_eq!(
+            events
+                .iter()
+                .filter(|event| matches!(event, Event::LogLine { .. }))
+                .count(),
+            3
+        );
+        assert!(matches!(
+            events.last(),
+            Some(Event::StageFinished { success: false, .. })
+        ));
+
+        let requests = daemon.requests.lock().unwrap();
+        assert!(requests[0].starts_with("POST /containers/create?name=near-verify-rs-"));
+        assert_eq!(
+            requests.last().unwrap(),
+            "DELETE /containers/c0ffee?force=1"
+        );
+    }
+}
@@ -0,0 +1,328 @@
+use std::sync::Arc;
+
+use camino::Utf8PathBuf;
+use colored::Colorize;
+use serde::Serialize;
+
+use crate::logic::internal::docker_command::print;
+use crate::types::report::{ImageReport, LogLine};
+
+/// Stage of verification, reported with [Event::StageStarted] and [Event::StageFinished]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "stage", rename_all = "snake_case")]
+pub enum Stage {
+    /// Checking that docker daemon is reachable
+    DockerCheck,
+    /// Pulling `build_environment` `image` from registry
+    PullImage { image: String },
+    /// Loading `build_environment` `image` from a local image `archive`
+    LoadImage { image: String, archive: Utf8PathBuf },
+    /// Running shell-escaped build `command` in build container
+    Build {
+        container_name: String,
+        command: String,
+    },
+}
+
+impl Stage {
+    /// [tracing::Span] of stage, nested in the current one, e.g. [verify_span](crate::logic::verify_span)
+    pub(crate) fn span(&self) -> tracing::Span {
+        match self {
+            Self::DockerCheck => tracing::info_span!("docker_check"),
+            Self::PullImage { image } => tracing::info_span!("pull", image = %image),
+            Self::LoadImage { image, archive } => {
+                tracing::info_span!("pull", image = %image, archive = %archive)
+            }
+            Self::Build { container_name, .. } => {
+                tracing::info_span!("build", container_name = %container_name)
+            }
+        }
+    }
+}
+
+/// Hint on how to resolve an [Event::CommandFailed]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Hint {
+    /// Docker isn't installed or doesn't work
+    InstallDocker,
+    /// Current user isn't allowed to access docker daemon
+    DockerPermissionDenied,
+}
+
+/// Progress of verification, reported to [Observer]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum Event {
+    StageStarted {
+        #[serde(flatten)]
+        stage: Stage,
+    },
+    StageFinished {
+        #[serde(flatten)]
+        stage: Stage,
+        success: bool,
+    },
+    /// Image was pulled or loaded from archive, and verified against its digest
+    ImagePulled {
+        image: ImageReport,
+    },
+    ContainerStarted {
+        container_name: String,
+        /// The exact `docker run` invocation, program followed by its arguments
+        docker_command: Vec<String>,
+    },
+    /// Line of build container's output, only reported with
+    /// [OutputMode::Capture](crate::logic::nep330_build::OutputMode::Capture)
+    LogLine {
+        container_name: String,
+        #[serde(flatten)]
+        line: LogLine,
+    },
+    ArtifactLocated {
+        container_name: String,
+        wasm_path: Utf8PathBuf,
+    },
+    /// Build will run under emulation, as image's platform differs from docker host's one
+    PlatformEmulated {
+        host: String,
+        image: String,
+    },
+    Warning {
+        message: String,
+    },
+    /// `docker` executable wasn't found
+    DockerNotFound,
+    /// `command` couldn't be run
+    CommandIoError {
+        command: String,
+        error: String,
+    },
+    CommandFailed {
+        /// Program of the command followed by its arguments
+        command: Vec<String>,
+        /// Exit status, as displayed
+        status: String,
+        /// Captured stderr of the command, [Option::None] if it was printed to terminal
+        stderr: Option<String>,
+        hint: Option<Hint>,
+    },
+}
+
+/// Receiver of [Event]s, passed with each call, e.g. as [BuildOptions::observer](crate::logic::nep330_build::BuildOptions::observer)
+///
+/// Events of a build may be reported from its output reader threads, so concurrent
+/// verifications are told apart by passing each of them its own observer
+pub trait Observer: Send + Sync {
+    fn event(&self, event: &Event);
+}
+
+/// [Observer], which prints colored human-readable output to stdout, as the CLI does
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Console;
+
+/// Default [Observer], which ignores all events
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Silent;
+
+/// [Observer], which can be cloned into threads of a build, [Silent] by default
+#[derive(Clone)]
+pub struct SharedObserver(Arc<dyn Observer>);
+
+impl SharedObserver {
+    pub fn new(observer: impl Observer + 'static) -> Self {
+        Self(Arc::new(observer))
+    }
+}
+
+impl From<Arc<dyn Observer>> for SharedObserver {
+    fn from(observer: Arc<dyn Observer>) -> Self {
+        Self(observer)
+    }
+}
+
+impl Default for SharedObserver {
+    fn default() -> Self {
+        Self::new(Silent)
+    }
+}
+
+impl std::fmt::Debug for SharedObserver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SharedObserver")
+    }
+}
+
+impl Observer for SharedObserver {
+    fn event(&self, event: &Event) {
+        self.0.event(event)
+    }
+}
+
+impl Observer for Console {
+    fn event(&self, event: &Event) {
+        match event {
+            Event::StageStarted {
+                stage: Stage::PullImage { image },
+            } => {
+                println!("{} {}", "docker image to be used:".green(), image);
+                println!();
+            }
+            Event::StageStarted {
+                stage: Stage::LoadImage { image, archive },
+            } => {
+                println!("{} {}", "docker image to be used:".green(), image);
+                println!("{} {}", "loaded from archive:".green(), archive);
+                println!();
+            }
+            Event::StageStarted {
+                stage: Stage::Build { command, .. },
+            } => {
+                println!("{} {}", "build command in container:".green(), command);
+                println!();
+            }
+            Event::PlatformEmulated { host, image } => {
+                println!(
+                    "{} {} {} {}{}",
+                    "Warning: image platform".yellow(),
+                    image.magenta(),
+                    "differs from docker host platform".yellow(),
+                    host.magenta(),
+                    ", build will run under emulation, which may result in a different artifact hash"
+                        .yellow()
+                );
+                println!();
+            }
+            Event::Warning { message } => println!("{}", message.yellow()),
+            Event::DockerNotFound => {
+                println!();
+                println!("{}", "`docker` executable isn't available".yellow());
+                print::installation_links();
+            }
+            Event::CommandIoError { command, error } => {
+                println!();
+                println!(
+                    "{}",
+                    format!(
+                        "Error obtaining status from executing command `{}`",
+                        command
+                    )
+                    .yellow()
+                );
+                println!("{}", format!("Error `{}`", error).yellow());
+            }
+            Event::CommandFailed {
+                command,
+                status,
+                stderr,
+                hint,
+            } => {
+                if let Some(stderr) = stderr {
+                    println!();
+                    println!("{}", stderr.yellow());
+                }
+                match hint {
+                    Some(Hint::DockerPermissionDenied) => {
+                        println!("{}", "Permission denied!".cyan());
+                        print::installation_links();
+                        print::linux_postinstall_steps();
+                    }
+                    Some(Hint::InstallDocker) => print::installation_links(),
+                    None => {}
+                }
+                print::command_args_status(status, command);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Observer for Silent {
+    fn event(&self, _event: &Event) {}
+}
+
+pub(crate) fn emit(observer: &dyn Observer, event: Event) {
+    tracing::trace!("event: {:?}", event);
+    observer.event(&event);
+}
+
+/// Runs `f` between [Event::StageStarted] and [Event::StageFinished] of `stage`,
+/// inside of [Stage::span]
+pub(crate) fn stage<T>(
+    observer: &dyn Observer,
+    stage: Stage,
+    f: impl FnOnce() -> eyre::Result<T>,
+) -> eyre::Result<T> {
+    let _span = stage.span().entered();
+    emit(
+        observer,
+        Event::StageStarted {
+            stage: stage.clone(),
+        },
+    );
+    let result = f();
+    emit(
+        observer,
+        Event::StageFinished {
+            stage,
+            success: result.is_ok(),
+        },
+    );
+    result
+}
+
+/// Async variant of [stage]
+#[cfg(feature = "tokio")]
+pub(crate) async fn stage_async<T>(
+    observer: &dyn Observer,
+    stage: Stage,
+    f: impl std::future::Future<Output = eyre::Result<T>>,
+) -> eyre::Result<T> {
+    use tracing::Instrument;
+
+    let span = stage.span();
+    async move {
+        emit(
+            observer,
+            Event::StageStarted {
+                stage: stage.clone(),
+            },
+        );
+        let result = f.await;
+        emit(
+            observer,
+            Event::StageFinished {
+                stage,
+                success: result.is_ok(),
+            },
+        );
+        result
+    }
+    .instrument(span)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Event, Stage};
+
+    #[test]
+    fn test_event_json() {
+        let event = Event::StageFinished {
+            stage: Stage::PullImage {
+                image: "hello-world".into(),
+            },
+            success: true,
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({
+                "event": "stage_finished",
+                "stage": "pull_image",
+                "image": "hello-world",
+                "success": true,
+            })
+        );
+    }
+}
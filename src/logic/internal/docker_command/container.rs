@@ -0,0 +1,31 @@
+use std::ffi::OsStr;
+use std::process::Command;
+
+/// Kills and removes container by its name with `docker` program, which has run it
+///
+/// Failures are only logged, as container might have already exited and been removed due to `--rm`
+pub fn kill_and_remove(docker: &OsStr, container_name: &str) {
+    for args in [
+        vec!["kill", container_name],
+        vec!["rm", "--force", container_name],
+    ] {
+        let mut docker_cmd = Command::new(docker);
+        docker_cmd.args(args);
+        match docker_cmd.output() {
+            Ok(output) if output.status.success() => {
+                tracing::debug!("{:?} succeeded", docker_cmd);
+            }
+            Ok(output) => {
+                tracing::debug!(
+                    "{:?} failed with {}: {}",
+                    docker_cmd,
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr)
+                );
+            }
+            Err(err) => {
+                tracing::warn!("{:?} couldn't be executed: {:?}", docker_cmd, err);
+            }
+        }
+    }
+}
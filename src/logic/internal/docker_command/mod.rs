@@ -1,34 +1,62 @@
-use colored::Colorize;
+use crate::logic::events::{self, Event, Observer};
+
+pub mod container;
 
 pub fn handle_io_error<T>(
     command: &std::process::Command,
     command_result: std::io::Result<T>,
     report: eyre::Report,
+    observer: &dyn Observer,
 ) -> eyre::Result<T> {
     match command_result {
         Ok(result) => Ok(result),
         Err(io_err) if io_err.kind() == std::io::ErrorKind::NotFound => {
-            println!();
-            println!("{}", "`docker` executable isn't available".yellow());
-            print::installation_links();
+            events::emit(observer, Event::DockerNotFound);
             Err(report)
         }
         Err(io_err) => {
-            println!();
-            println!(
-                "{}",
-                format!(
-                    "Error obtaining status from executing command `{:?}`",
-                    command
-                )
-                .yellow()
+            events::emit(
+                observer,
+                Event::CommandIoError {
+                    command: format!("{:?}", command),
+                    error: format!("{:?}", io_err),
+                },
             );
-            println!("{}", format!("Error `{:?}`", io_err).yellow());
             Err(report)
         }
     }
 }
 
+/// Reports failure of `command`, see [Event::CommandFailed]
+pub(crate) fn command_failed(
+    command: &std::process::Command,
+    status: std::process::ExitStatus,
+    stderr: Option<&[u8]>,
+    hint: Option<events::Hint>,
+    observer: &dyn Observer,
+) {
+    events::emit(
+        observer,
+        Event::CommandFailed {
+            command: command_args(command),
+            status: status.to_string(),
+            stderr: stderr.map(|stderr| String::from_utf8_lossy(stderr).to_string()),
+            hint,
+        },
+    );
+}
+
+/// Program of the command followed by its arguments
+pub fn command_args(command: &std::process::Command) -> Vec<String> {
+    let mut args = vec![command.get_program().to_string_lossy().to_string()];
+    args.extend(
+        command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().to_string()),
+    );
+    args
+}
+
 pub mod print {
     use colored::Colorize;
     use std::process::Command;
@@ -99,17 +127,9 @@ pub mod print {
             "problem".cyan(),
         );
     }
-    pub fn command_status(status: std::process::ExitStatus, command: std::process::Command) {
+    pub(crate) fn command_args_status(status: &str, command_args: &[String]) {
         println!();
-        let command = {
-            let mut args = vec![command.get_program().to_string_lossy().to_string()];
-            args.extend(
-                command
-                    .get_args()
-                    .map(|arg| arg.to_string_lossy().to_string()),
-            );
-            args.join(" ")
-        };
+        let command = command_args.join(" ");
 
         println!(
             "{}",
@@ -1,152 +1,233 @@
+use crate::logic::events::{self, Event, Observer};
 use crate::logic::internal::docker_command;
-use crate::types::internal::container_paths;
-use colored::Colorize;
-use std::io::IsTerminal;
-use std::{
-    process::{Command, ExitStatus},
-    time::{SystemTime, UNIX_EPOCH},
-};
-
-#[cfg(target_os = "linux")]
-use nix::unistd::{getgid, getuid};
+use std::process::{Command, ExitStatus, Stdio};
 
-use crate::env_keys;
 use crate::pretty_print;
 use crate::types::contract_source_metadata::ContractSourceMetadata;
+use crate::types::report::{BuildLog, BuildReport};
 
 pub const ERR_REPRODUCIBLE: &str = "Reproducible build in docker container failed.";
+pub(crate) mod capture;
+mod cargo_cache;
+mod error;
+#[cfg(all(test, unix))]
+pub(crate) mod fake_docker;
+mod handle;
+mod options;
 mod output;
+#[cfg(target_os = "linux")]
+mod ownership;
+mod spec;
+
+pub use cargo_cache::CargoCache;
+pub(crate) use cargo_cache::PreparedCargoCache;
+pub use error::{BuildFailedError, CancelledError, OwnershipError, TimeoutError};
+pub use handle::{BuildHandle, Canceller};
+pub use options::{
+    BuildOptions, CaptureOptions, Limits, LogSink, Mount, OutputMode, PullPolicy, UserMapping,
+};
+pub(crate) use spec::ContainerSpec;
+
+/// `docker run` of a build, which has exited
+pub(crate) struct ExitedRun {
+    pub status: ExitStatus,
+    pub docker_command: Vec<String>,
+    pub container_name: String,
+    pub log: Option<BuildLog>,
+    pub cargo_cache: Option<PreparedCargoCache>,
+}
 
-fn handle_docker_run_status(
+pub(crate) fn handle_docker_run_status(
     contract_source_metadata: ContractSourceMetadata,
     contract_source_workdir: camino::Utf8PathBuf,
-    status: ExitStatus,
-    command: Command,
-) -> eyre::Result<camino::Utf8PathBuf> {
+    run: ExitedRun,
+    observer: &dyn Observer,
+) -> eyre::Result<BuildReport> {
+    let ExitedRun {
+        status,
+        docker_command,
+        container_name,
+        log,
+        cargo_cache,
+    } = run;
+    let cargo_cache = cargo_cache.map(PreparedCargoCache::finish);
     if status.success() {
         // let build_info = contract_source_metadata.build_info.as_ref().expect(
         //     "cannot be [Option::None] as per [ContractSourceMetadata::validate_meta] check"
         // );
         // if build_info.wasm_result_path.is_none() branch ============
-        output::rust_legacy_wasm_output_path(contract_source_metadata, contract_source_workdir)
+        let wasm_path = output::rust_legacy_wasm_output_path(
+            contract_source_metadata,
+            contract_source_workdir,
+            observer,
+        )?;
         // ============
 
         // if build_info.wasm_result_path.is_some() branch ============
         // unimplemented!();
         // this is pending nep330 1.3.0 extension
         // ============
+        events::emit(
+            observer,
+            Event::ArtifactLocated {
+                container_name: container_name.clone(),
+                wasm_path: wasm_path.clone(),
+            },
+        );
+        Ok(BuildReport {
+            wasm_path,
+            container_name,
+            docker_command,
+            log,
+            cargo_cache,
+        })
     } else {
-        docker_command::print::command_status(status, command);
-        Err(eyre::eyre!(ERR_REPRODUCIBLE))
+        if log.is_none() {
+            events::emit(
+                observer,
+                Event::CommandFailed {
+                    command: docker_command,
+                    status: status.to_string(),
+                    stderr: None,
+                    hint: None,
+                },
+            );
+        }
+        Err(eyre::Report::new(BuildFailedError {
+            exit_status: status.to_string(),
+            exit_code: status.code(),
+            log,
+        }))
     }
 }
 
+/// Runs reproducible build in docker container and blocks until it finishes
+///
+/// This is a shortcut for [spawn] followed by [BuildHandle::wait]
 pub fn run(
     contract_source_metadata: ContractSourceMetadata,
     contract_source_workdir: camino::Utf8PathBuf,
-    additional_docker_args: Vec<String>,
-) -> eyre::Result<camino::Utf8PathBuf> {
-    let (status, command) = run_inner(
-        contract_source_metadata.clone(),
-        contract_source_workdir.clone(),
-        additional_docker_args,
-    )?;
+    build_options: BuildOptions,
+) -> eyre::Result<BuildReport> {
+    spawn(
+        contract_source_metadata,
+        contract_source_workdir,
+        build_options,
+    )?
+    .wait()
+}
 
-    handle_docker_run_status(
+/// Starts reproducible build in docker container without waiting for it to finish
+///
+/// Returned [BuildHandle] can be cancelled from another thread with [BuildHandle::canceller],
+/// and it kills and removes build container when dropped before completion
+pub fn spawn(
+    contract_source_metadata: ContractSourceMetadata,
+    contract_source_workdir: camino::Utf8PathBuf,
+    build_options: BuildOptions,
+) -> eyre::Result<BuildHandle> {
+    let (docker_cmd, spec) = docker_run_cmd(
+        &contract_source_metadata,
+        &contract_source_workdir,
+        &build_options,
+    )?;
+    spawn_container(
         contract_source_metadata,
         contract_source_workdir,
-        status,
-        command,
+        build_options,
+        docker_cmd,
+        spec,
     )
 }
 
-fn run_inner(
+fn spawn_container(
     contract_source_metadata: ContractSourceMetadata,
     contract_source_workdir: camino::Utf8PathBuf,
-    additional_docker_args: Vec<String>,
-) -> eyre::Result<(ExitStatus, Command)> {
-    let build_info = contract_source_metadata
-        .build_info
-        .clone()
-        .expect("cannot be [Option::None] as per `validate_meta` check");
-    let mut docker_cmd: Command = {
-        // Platform-specific UID/GID retrieval
-
-        // reason for this mapping is that on Linux the volume is mounted natively,
-        // and thus the unprivileged user inside Docker container should be able to write
-        // to the mounted folder that has the host user permissions,
-        // not specifying this mapping results in UID=Docker-User owned files created in host system
-        #[cfg(target_os = "linux")]
-        let uid_gid = format!("{}:{}", getuid(), getgid());
-        #[cfg(not(target_os = "linux"))]
-        let uid_gid = "1000:1000".to_string();
-
-        let docker_container_name = {
-            // Cross-platform process ID and timestamp
-            let pid = std::process::id().to_string();
-            let timestamp = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_nanos()
-                .to_string();
-            format!("near-verify-rs-{}-{}", timestamp, pid)
-        };
-        let container_paths =
-            container_paths::Paths::compute(&build_info, contract_source_workdir)?;
-
-        let docker_env_args = contract_source_metadata.docker_env_args();
-        let shell_escaped_cargo_cmd =
-            crate::logic::shell_escape_nep330_build_command(build_info.build_command);
-        println!(
-            "{} {}",
-            "build command in container:".green(),
-            shell_escaped_cargo_cmd
-        );
-        println!();
+    build_options: BuildOptions,
+    mut docker_cmd: Command,
+    spec: ContainerSpec,
+) -> eyre::Result<BuildHandle> {
+    // [BuildHandle] keeps the current `build` span to wait in it
+    let _entered = spec.stage().span().entered();
+    let observer = &build_options.observer;
+    events::emit(
+        observer,
+        Event::StageStarted {
+            stage: spec.stage(),
+        },
+    );
 
-        let docker_args = {
-            let mut docker_args = vec![
-                "-u",
-                &uid_gid,
-                "--name",
-                &docker_container_name,
-                "--volume",
-                &container_paths.host_volume_arg,
-                "--rm",
-                "--workdir",
-                &container_paths.crate_path,
-            ];
-            let stdin_is_terminal = std::io::stdin().is_terminal();
-            tracing::debug!("input device is a tty: {}", stdin_is_terminal);
-            if stdin_is_terminal
-                && std::env::var(env_keys::nonspec::SERVER_DISABLE_INTERACTIVE).is_err()
-            {
-                docker_args.push("-it");
-            }
+    let child_result = docker_cmd.spawn();
+    let mut child = docker_command::handle_io_error(
+        &docker_cmd,
+        child_result,
+        eyre::eyre!(ERR_REPRODUCIBLE),
+        observer,
+    )
+    .inspect_err(|_| {
+        events::emit(
+            observer,
+            Event::StageFinished {
+                stage: spec.stage(),
+                success: false,
+            },
+        )
+    })?;
+    events::emit(
+        observer,
+        Event::ContainerStarted {
+            container_name: spec.name.clone(),
+            docker_command: docker_command::command_args(&docker_cmd),
+        },
+    );
 
-            docker_args.extend(docker_env_args.iter().map(|string| string.as_str()));
-            docker_args.extend(additional_docker_args.iter().map(|string| string.as_str()));
-            docker_args.extend(vec![&build_info.build_environment, "/bin/bash", "-c"]);
+    let capture = match build_options.output {
+        OutputMode::Inherit => None,
+        OutputMode::Capture(ref capture_options) => Some(capture::Capture::start(
+            &mut child,
+            &spec.name,
+            capture_options.clone(),
+            observer,
+        )),
+    };
 
-            docker_args.push(&shell_escaped_cargo_cmd);
-            docker_args
-        };
+    Ok(BuildHandle::new(
+        child,
+        capture,
+        docker_cmd,
+        spec,
+        build_options,
+        contract_source_metadata,
+        contract_source_workdir,
+    ))
+}
 
-        let mut docker_cmd = Command::new("docker");
-        docker_cmd.arg("run");
-        docker_cmd.args(docker_args);
-        docker_cmd
-    };
+/// `docker run` command of reproducible build, and configuration of its container
+pub(crate) fn docker_run_cmd(
+    contract_source_metadata: &ContractSourceMetadata,
+    contract_source_workdir: &camino::Utf8Path,
+    build_options: &BuildOptions,
+) -> eyre::Result<(Command, ContainerSpec)> {
+    let spec = ContainerSpec::compute(
+        contract_source_metadata,
+        contract_source_workdir,
+        build_options,
+    )?;
+    let mut docker_cmd = Command::new("docker");
+    docker_cmd.arg("run");
+    docker_cmd.args(spec.docker_run_args());
     tracing::info!(
         target: "near_teach_me",
-        parent: &tracing::Span::none(),
         "Docker command:\n{}",
         pretty_print::indent_payload(&format!("{:#?}", docker_cmd))
     );
 
-    let status_result = docker_cmd.status();
-    let status =
-        docker_command::handle_io_error(&docker_cmd, status_result, eyre::eyre!(ERR_REPRODUCIBLE))?;
+    if let OutputMode::Capture(_) = build_options.output {
+        docker_cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+    }
 
-    Ok((status, docker_cmd))
+    Ok((docker_cmd, spec))
 }
@@ -0,0 +1,210 @@
+use std::io::Read;
+use std::process::Child;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use crate::logic::events::{self, Event, Observer, SharedObserver};
+use crate::types::report::{BuildLog, LogLine, Stream};
+
+use super::options::CaptureOptions;
+
+/// Collects output of `docker run` process, which was spawned with piped stdout and stderr
+pub(super) struct Capture {
+    log: Arc<Mutex<BuildLog>>,
+    readers: Vec<JoinHandle<()>>,
+}
+
+impl Capture {
+    pub fn start(
+        child: &mut Child,
+        container_name: &str,
+        options: CaptureOptions,
+        observer: &SharedObserver,
+    ) -> Self {
+        let log = Arc::new(Mutex::new(BuildLog::default()));
+        let mut readers = vec![];
+        if let Some(stdout) = child.stdout.take() {
+            readers.push(reader_thread(
+                stdout,
+                Stream::Stdout,
+                container_name,
+                &log,
+                &options,
+                observer,
+            ));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            readers.push(reader_thread(
+                stderr,
+                Stream::Stderr,
+                container_name,
+                &log,
+                &options,
+                observer,
+            ));
+        }
+        Self { log, readers }
+    }
+
+    /// Waits for both streams to be closed and returns collected log
+    ///
+    /// Streams are closed by `docker run` process on its exit
+    pub fn finish(self) -> BuildLog {
+        for reader in self.readers {
+            if reader.join().is_err() {
+                tracing::warn!("build output reader thread panicked");
+            }
+        }
+        let mut log = self.log.lock().expect("reader threads have finished");
+        std::mem::take(&mut *log)
+    }
+}
+
+fn reader_thread<R: Read + Send + 'static>(
+    reader: R,
+    stream: Stream,
+    container_name: &str,
+    log: &Arc<Mutex<BuildLog>>,
+    options: &CaptureOptions,
+    observer: &SharedObserver,
+) -> JoinHandle<()> {
+    let container_name = container_name.to_string();
+    let log = log.clone();
+    let options = options.clone();
+    let observer = observer.clone();
+    std::thread::spawn(move || {
+        let mut reader = reader;
+        let mut record = |line: &[u8], truncated: bool| {
+            record_line(
+                line,
+                truncated,
+                stream,
+                &container_name,
+                &log,
+                &options,
+                &observer,
+            )
+        };
+        let mut lines = Lines::new(options.max_bytes);
+        let mut chunk = [0u8; CHUNK_SIZE];
+        loop {
+            let read = match reader.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    tracing::warn!("error reading build output from {:?}: {:?}", stream, err);
+                    break;
+                }
+            };
+            lines.push(&chunk[..read], &mut record);
+        }
+        lines.finish(record);
+    })
+}
+
+/// Size of chunks, which output is read in
+pub(crate) const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Splits chunks of output into lines, keeping at most `max_bytes` of each line,
+/// so that output without newlines can't grow memory unbounded
+pub(crate) struct Lines {
+    partial: Vec<u8>,
+    truncated: bool,
+    max_bytes: usize,
+}
+
+impl Lines {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            partial: vec![],
+            truncated: false,
+            max_bytes,
+        }
+    }
+
+    /// Appends `chunk` and calls `f` with each line it completes
+    /// and whether the line was cut to `max_bytes`
+    pub fn push(&mut self, chunk: &[u8], mut f: impl FnMut(&[u8], bool)) {
+        for piece in chunk.split_inclusive(|byte| *byte == b'\n') {
+            let (content, complete) = match piece.strip_suffix(b"\n") {
+                Some(content) => (content, true),
+                None => (piece, false),
+            };
+            let room = self.max_bytes.saturating_sub(self.partial.len());
+            if content.len() > room {
+                self.truncated = true;
+            }
+            self.partial
+                .extend_from_slice(&content[..content.len().min(room)]);
+            if complete {
+                f(&self.partial, self.truncated);
+                self.partial.clear();
+                self.truncated = false;
+            }
+        }
+    }
+
+    /// Calls `f` with the rest of output after the last newline, if any
+    pub fn finish(self, f: impl FnOnce(&[u8], bool)) {
+        if !self.partial.is_empty() || self.truncated {
+            f(&self.partial, self.truncated);
+        }
+    }
+}
+
+/// Forwards a raw line of output to [CaptureOptions::sink] and [Event::LogLine],
+/// and appends it to `log`, which is marked as truncated if the line was cut
+pub(crate) fn record_line(
+    buf: &[u8],
+    truncated: bool,
+    stream: Stream,
+    container_name: &str,
+    log: &Mutex<BuildLog>,
+    options: &CaptureOptions,
+    observer: &dyn Observer,
+) {
+    let line = String::from_utf8_lossy(buf)
+        .trim_end_matches(['\n', '\r'])
+        .to_string();
+    let line = LogLine { stream, line };
+    if let Some(ref sink) = options.sink {
+        sink.line(&line);
+    }
+    events::emit(
+        observer,
+        Event::LogLine {
+            container_name: container_name.to_string(),
+            line: line.clone(),
+        },
+    );
+    let mut log = log.lock().expect("no panics while holding the lock");
+    log.push(line, options.max_bytes);
+    if truncated {
+        log.truncated = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Lines;
+
+    #[test]
+    fn test_lines_are_cut() {
+        let mut lines = Lines::new(4);
+        let mut collected = vec![];
+        let mut collect = |line: &[u8], truncated: bool| collected.push((line.to_vec(), truncated));
+        lines.push(b"abcd\nabc", &mut collect);
+        lines.push(&[b'x'; 100_000], &mut collect);
+        lines.push(b"yz\nlast", &mut collect);
+        lines.finish(collect);
+        assert_eq!(
+            collected,
+            vec![
+                (b"abcd".to_vec(), false),
+                (b"abcx".to_vec(), true),
+                (b"last".to_vec(), false),
+            ]
+        );
+    }
+}
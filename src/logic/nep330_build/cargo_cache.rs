@@ -0,0 +1,166 @@
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::types::contract_source_metadata::docker_image_digest;
+use crate::types::report::CargoCacheReport;
+
+/// `CARGO_HOME` of `rust` images, which `sourcescan/cargo-near` images are based on
+const DEFAULT_CARGO_HOME: &str = "/usr/local/cargo";
+/// Lock file of cargo's package cache in `CARGO_HOME`
+const PACKAGE_CACHE_LOCK: &str = ".package-cache";
+
+/// Persistent host directory with cargo registry and git checkouts, mounted into build container
+/// to avoid downloading dependencies on every build
+///
+/// Caches are content-addressed by digest of `build_environment` image and `cargo_home`,
+/// so builds in different images never share a cache.
+/// Reusing a cache doesn't affect reproducibility: it's mounted at the same paths as the ones
+/// cargo downloads to without it, and `.crate` files are checked against `Cargo.lock` checksums.
+///
+/// Cargo's package cache lock file is shared too, so concurrent builds with the same cache
+/// only wait for each other while downloading
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CargoCache {
+    /// Host directory, which contains caches for different images
+    pub root: Utf8PathBuf,
+    /// `CARGO_HOME` in build container
+    pub cargo_home: String,
+}
+
+impl CargoCache {
+    pub fn new(root: impl Into<Utf8PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            cargo_home: DEFAULT_CARGO_HOME.to_string(),
+        }
+    }
+
+    /// Key of cache directory in [CargoCache::root]
+    pub fn key(&self, build_environment: &str) -> eyre::Result<String> {
+        let digest = docker_image_digest(build_environment)?;
+        let mut hasher = <sha2::Sha256 as sha2::Digest>::new();
+        sha2::Digest::update(&mut hasher, digest.as_bytes());
+        sha2::Digest::update(&mut hasher, b"\n");
+        sha2::Digest::update(&mut hasher, self.cargo_home.as_bytes());
+        Ok(hex::encode(sha2::Digest::finalize(hasher)))
+    }
+
+    /// Creates cache directory for `build_environment`, so that it's not created by docker as root
+    pub(crate) fn prepare(&self, build_environment: &str) -> eyre::Result<PreparedCargoCache> {
+        let key = self.key(build_environment)?;
+        let path = self.root.join(&key);
+        for dir in ["registry", "git"] {
+            std::fs::create_dir_all(path.join(dir)).map_err(|err| {
+                eyre::eyre!("couldn't create cargo cache `{}`: {}", path.join(dir), err)
+            })?;
+        }
+        let lock = path.join(PACKAGE_CACHE_LOCK);
+        if !lock.exists() {
+            std::fs::write(&lock, b"")?;
+        }
+        let cargo_home = unix_path::Path::new(&self.cargo_home);
+        let volume = |name: &str| {
+            format!(
+                "{}:{}",
+                path.join(name),
+                cargo_home.join(name).to_string_lossy()
+            )
+        };
+        Ok(PreparedCargoCache {
+            volumes: vec![
+                volume("registry"),
+                volume("git"),
+                volume(PACKAGE_CACHE_LOCK),
+            ],
+            crates_before: count_crates(&path)?,
+            key,
+            path,
+        })
+    }
+}
+
+/// Cache directory of a build, which is about to be started
+#[derive(Debug, Clone)]
+pub(crate) struct PreparedCargoCache {
+    pub key: String,
+    pub path: Utf8PathBuf,
+    /// `<host path>:<container path>` read-write bind mounts
+    pub volumes: Vec<String>,
+    crates_before: usize,
+}
+
+impl PreparedCargoCache {
+    pub fn finish(self) -> CargoCacheReport {
+        let crates_after = count_crates(&self.path).unwrap_or_else(|err| {
+            tracing::warn!(
+                "couldn't count crates in cargo cache `{}`: {:#}",
+                self.path,
+                err
+            );
+            self.crates_before
+        });
+        CargoCacheReport {
+            key: self.key,
+            path: self.path,
+            hit: self.crates_before > 0,
+            crates_before: self.crates_before,
+            crates_after,
+        }
+    }
+}
+
+/// Number of downloaded `.crate` archives, `registry/cache/<index>/<name>-<version>.crate`
+fn count_crates(path: &Utf8Path) -> eyre::Result<usize> {
+    let cache_dir = path.join("registry").join("cache");
+    if !cache_dir.exists() {
+        return Ok(0);
+    }
+    let mut count = 0;
+    for index in cache_dir.read_dir_utf8()? {
+        let index = index?;
+        if !index.file_type()?.is_dir() {
+            continue;
+        }
+        for entry in index.path().read_dir_utf8()? {
+            if entry?.path().extension() == Some("crate") {
+                count += 1;
+            }
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CargoCache;
+
+    const IMAGE: &str = "sourcescan/cargo-near:0.13.4-rust-1.85.0@sha256:a9d8bee7b134856cc8baa142494a177f2ba9ecfededfcdd38f634e14cca8aae2";
+
+    #[test]
+    fn test_prepare_and_report() {
+        let root = tempfile::tempdir().unwrap();
+        let root = camino::Utf8PathBuf::from_path_buf(root.path().to_path_buf()).unwrap();
+        let cache = CargoCache::new(&root);
+
+        let prepared = cache.prepare(IMAGE).unwrap();
+        assert_eq!(prepared.path, root.join(cache.key(IMAGE).unwrap()));
+        assert_eq!(
+            prepared.volumes[0],
+            format!("{}/registry:/usr/local/cargo/registry", prepared.path)
+        );
+        let index_dir = prepared
+            .path
+            .join("registry/cache/index.crates.io-1949cf8c6b5b557f");
+        std::fs::create_dir_all(&index_dir).unwrap();
+        std::fs::write(index_dir.join("serde-1.0.219.crate"), b"").unwrap();
+        let report = prepared.finish();
+        assert!(!report.hit);
+        assert_eq!(report.crates_after, 1);
+
+        let report = cache.prepare(IMAGE).unwrap().finish();
+        assert!(report.hit);
+        assert_eq!((report.crates_before, report.crates_after), (1, 1));
+
+        let other_image = IMAGE.replace("a9d8", "b9d8");
+        assert_ne!(cache.key(&other_image).unwrap(), cache.key(IMAGE).unwrap());
+    }
+}
@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+use crate::types::report::BuildLog;
+
+/// Error, returned by [super::run] when build command in docker container exited with failure
+///
+/// Its message is [super::ERR_REPRODUCIBLE].
+/// It can be distinguished from other errors with [eyre::Report::downcast_ref]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildFailedError {
+    /// Exit status of `docker run` process, as displayed
+    pub exit_status: String,
+    pub exit_code: Option<i32>,
+    /// Output of build container, [Option::Some] if it was captured
+    pub log: Option<BuildLog>,
+}
+
+impl std::fmt::Display for BuildFailedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", super::ERR_REPRODUCIBLE)
+    }
+}
+
+impl std::error::Error for BuildFailedError {}
+
+/// Error, returned by [super::run] when [Limits::timeout](super::Limits::timeout) has elapsed
+///
+/// It can be distinguished from other errors with [eyre::Report::downcast_ref]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeoutError {
+    /// Name of build container, which was killed and removed
+    pub container_name: String,
+    pub timeout: Duration,
+    /// Output of build container, [Option::Some] if it was captured
+    pub log: Option<BuildLog>,
+}
+
+impl std::fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Reproducible build in docker container timed out after {:?}, container `{}` was killed.",
+            self.timeout, self.container_name
+        )
+    }
+}
+
+impl std::error::Error for TimeoutError {}
+
+/// Error, returned by [BuildHandle::wait](super::BuildHandle::wait) when the build was cancelled
+/// with [Canceller::cancel](super::Canceller::cancel)
+///
+/// It can be distinguished from other errors with [eyre::Report::downcast_ref]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CancelledError {
+    /// Name of build container, which was killed and removed
+    pub container_name: String,
+    /// Output of build container, [Option::Some] if it was captured
+    pub log: Option<BuildLog>,
+}
+
+impl std::fmt::Display for CancelledError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Reproducible build in docker container was cancelled, container `{}` was killed.",
+            self.container_name
+        )
+    }
+}
+
+impl std::error::Error for CancelledError {}
+
+/// Error, returned by [super::run] when build artifacts in `target` directory can't be read
+/// by current user, usually due to [UserMapping](super::UserMapping) not matching the container runtime
+/// (rootless Podman, userns-remapped docker)
+///
+/// It can be distinguished from other errors with [eyre::Report::downcast_ref]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnershipError {
+    pub path: camino::Utf8PathBuf,
+    /// Owner of `path` on host
+    pub owner_uid: u32,
+    /// User of current process
+    pub current_uid: u32,
+}
+
+impl std::fmt::Display for OwnershipError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Build artifact `{}` is owned by uid {} and can't be accessed by current user (uid {}), \
+            check container user mapping of build.",
+            self.path, self.owner_uid, self.current_uid
+        )
+    }
+}
+
+impl std::error::Error for OwnershipError {}
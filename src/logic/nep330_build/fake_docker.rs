@@ -0,0 +1,97 @@
+//! Fake `docker` executable for tests of build container lifecycle
+use std::os::unix::fs::PermissionsExt;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use super::{BuildOptions, ContainerSpec};
+use crate::types::contract_source_metadata::ContractSourceMetadata;
+
+const META: &str = r#"{
+  "build_info": {
+    "build_command": ["cargo", "near", "build", "non-reproducible-wasm", "--locked"],
+    "build_environment": "sourcescan/cargo-near:0.13.4-rust-1.85.0@sha256:a9d8bee7b134856cc8baa142494a177f2ba9ecfededfcdd38f634e14cca8aae2",
+    "contract_path": "",
+    "source_code_snapshot": "git+https://github.com/dj8yfo/verify_contracts_collection?rev=e3303f0cf8761b99c84ec40a4b7e2a3b3e3b5e3c"
+  },
+  "link": null,
+  "standards": [],
+  "version": "0.1.0"
+}"#;
+
+/// Fake `docker` executable, which appends its arguments to `calls` file,
+/// except for `docker run`, which is recorded as `run` and runs `run_script`
+pub(crate) struct FakeDocker {
+    dir: tempfile::TempDir,
+    pub workdir: camino::Utf8PathBuf,
+}
+
+impl FakeDocker {
+    pub fn new(run_script: &str) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let program = dir.path().join("docker");
+        std::fs::write(
+            &program,
+            format!(
+                "#!/bin/sh\nif [ \"$1\" = run ]; then\necho run >> '{calls}'\n{run_script}\nelse\necho \"$@\" >> '{calls}'\nfi\n",
+                calls = dir.path().join("calls").display(),
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let workdir =
+            camino::Utf8PathBuf::from_path_buf(dir.path().join("code")).expect("utf-8 path");
+        std::fs::create_dir(&workdir).unwrap();
+        Self { dir, workdir }
+    }
+
+    pub fn meta(&self) -> ContractSourceMetadata {
+        serde_json::from_str(META).unwrap()
+    }
+
+    /// `docker run` command of fake `docker` and its container
+    pub fn docker_run_cmd(&self, build_options: &BuildOptions) -> (Command, ContainerSpec) {
+        let spec = ContainerSpec::compute(&self.meta(), &self.workdir, build_options).unwrap();
+        let mut docker_cmd = Command::new(self.dir.path().join("docker"));
+        docker_cmd.arg("run").args(spec.docker_run_args());
+        (docker_cmd, spec)
+    }
+
+    pub fn calls(&self) -> Vec<String> {
+        std::fs::read_to_string(self.dir.path().join("calls"))
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Waits for `docker run` to be started
+    pub fn wait_for_run(&self) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !self.calls().contains(&"run".to_string()) && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    /// Waits for `docker rm` of container, which may be run from a background thread
+    #[cfg(feature = "tokio")]
+    pub fn wait_for_removal(&self, container_name: &str) -> Vec<String> {
+        let removal = format!("rm --force {}", container_name);
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let calls = self.calls();
+            if calls.contains(&removal) || Instant::now() > deadline {
+                return calls;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+}
+
+/// Calls of fake `docker` for a container, which was killed and removed
+pub(crate) fn killed(container_name: &str) -> Vec<String> {
+    vec![
+        "run".to_string(),
+        format!("kill {}", container_name),
+        format!("rm --force {}", container_name),
+    ]
+}
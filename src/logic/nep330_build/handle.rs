@@ -0,0 +1,283 @@
+use std::ffi::OsString;
+use std::process::{Child, Command, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::logic::events::{self, Event, SharedObserver, Stage};
+use crate::logic::internal::docker_command;
+use crate::types::contract_source_metadata::ContractSourceMetadata;
+use crate::types::report::{BuildLog, BuildReport};
+
+use super::capture::Capture;
+use super::{
+    BuildOptions, CancelledError, ContainerSpec, ExitedRun, PreparedCargoCache, TimeoutError,
+};
+
+/// Interval of polling `docker run` process for exit
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Handle of a reproducible build, running in docker container, obtained from [super::spawn]
+///
+/// If the handle is dropped before the build has finished, the build container is killed and removed.
+pub struct BuildHandle {
+    child: Child,
+    capture: Option<Capture>,
+    command: Option<Command>,
+    /// `docker` program, which has run the container
+    docker: OsString,
+    container_name: String,
+    stage: Stage,
+    cargo_cache: Option<PreparedCargoCache>,
+    timeout: Option<Duration>,
+    started_at: Instant,
+    cancelled: Arc<AtomicBool>,
+    /// `docker run` process has exited and its status has been obtained
+    finished: bool,
+    contract_source_metadata: ContractSourceMetadata,
+    contract_source_workdir: camino::Utf8PathBuf,
+    /// `build` span, which waiting for the build and locating its artifact are traced in
+    span: tracing::Span,
+    observer: SharedObserver,
+}
+
+/// Cancels a [BuildHandle] from another thread or async task
+///
+/// Can be cloned and sent freely, cancelling a build which has already finished is a no-op.
+#[derive(Debug, Clone)]
+pub struct Canceller {
+    container_name: String,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Canceller {
+    /// Requests cancellation of the build, doesn't block
+    ///
+    /// Build container is killed and removed by [BuildHandle::wait], which returns [CancelledError],
+    /// or when the handle is dropped. A build, which has exited before that, isn't affected.
+    pub fn cancel(&self) {
+        if self.cancelled.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        tracing::debug!("cancelling build container `{}`", self.container_name);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+impl BuildHandle {
+    pub(super) fn new(
+        child: Child,
+        capture: Option<Capture>,
+        command: Command,
+        spec: ContainerSpec,
+        build_options: BuildOptions,
+        contract_source_metadata: ContractSourceMetadata,
+        contract_source_workdir: camino::Utf8PathBuf,
+    ) -> Self {
+        Self {
+            child,
+            capture,
+            docker: command.get_program().to_owned(),
+            command: Some(command),
+            stage: spec.stage(),
+            container_name: spec.name,
+            cargo_cache: spec.cargo_cache,
+            timeout: build_options.limits.timeout,
+            started_at: Instant::now(),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            finished: false,
+            contract_source_metadata,
+            contract_source_workdir,
+            span: tracing::Span::current(),
+            observer: build_options.observer,
+        }
+    }
+
+    /// Generated name of build container, `<prefix>-<timestamp>-<pid>`, see [BuildOptions::container_name_prefix](crate::logic::nep330_build::BuildOptions::container_name_prefix)
+    pub fn container_name(&self) -> &str {
+        &self.container_name
+    }
+
+    pub fn canceller(&self) -> Canceller {
+        Canceller {
+            container_name: self.container_name.clone(),
+            cancelled: self.cancelled.clone(),
+        }
+    }
+
+    /// Blocks until the build finishes, times out or gets cancelled,
+    /// and returns [BuildReport] with path to the resulting wasm artifact on success
+    pub fn wait(mut self) -> eyre::Result<BuildReport> {
+        let _entered = self.span.clone().entered();
+        let result = self.wait_report();
+        events::emit(
+            &self.observer,
+            Event::StageFinished {
+                stage: self.stage.clone(),
+                success: result.is_ok(),
+            },
+        );
+        result
+    }
+
+    fn wait_report(&mut self) -> eyre::Result<BuildReport> {
+        let status = self.wait_status()?;
+        let command = self
+            .command
+            .take()
+            .expect("is only taken once, as [BuildHandle::wait] consumes `self`");
+        let log = self.finish_capture();
+        super::handle_docker_run_status(
+            self.contract_source_metadata.clone(),
+            self.contract_source_workdir.clone(),
+            ExitedRun {
+                status,
+                docker_command: docker_command::command_args(&command),
+                container_name: self.container_name.clone(),
+                log,
+                cargo_cache: self.cargo_cache.take(),
+            },
+            &self.observer,
+        )
+    }
+
+    fn finish_capture(&mut self) -> Option<BuildLog> {
+        self.capture.take().map(Capture::finish)
+    }
+
+    fn wait_status(&mut self) -> eyre::Result<ExitStatus> {
+        let deadline = self.timeout.map(|timeout| self.started_at + timeout);
+        loop {
+            // a build, which has exited on its own, isn't reported as cancelled
+            if let Some(status) = self.child.try_wait()? {
+                self.finished = true;
+                return Ok(status);
+            }
+            if self.cancelled.load(Ordering::SeqCst) {
+                self.terminate();
+                return Err(eyre::Report::new(CancelledError {
+                    container_name: self.container_name.clone(),
+                    log: self.finish_capture(),
+                }));
+            }
+            if let Some(deadline) = deadline {
+                if Instant::now() >= deadline {
+                    let timeout = self.timeout.expect("deadline is only set with timeout");
+                    tracing::warn!(
+                        "build container `{}` exceeded timeout of {:?}, killing it",
+                        self.container_name,
+                        timeout
+                    );
+                    self.terminate();
+                    return Err(eyre::Report::new(TimeoutError {
+                        container_name: self.container_name.clone(),
+                        timeout,
+                        log: self.finish_capture(),
+                    }));
+                }
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Kills and removes build container, and reaps `docker run` process
+    fn terminate(&mut self) {
+        docker_command::container::kill_and_remove(&self.docker, &self.container_name);
+        // `docker run` client process normally exits after its container is killed,
+        // this is for the case it doesn't
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        self.finished = true;
+    }
+}
+
+impl Drop for BuildHandle {
+    fn drop(&mut self) {
+        if !self.finished {
+            tracing::debug!(
+                "build handle dropped before completion, removing container `{}`",
+                self.container_name
+            );
+            self.terminate();
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::time::Duration;
+
+    use super::super::fake_docker::{self, FakeDocker};
+    use super::super::{
+        BuildFailedError, BuildHandle, BuildOptions, CancelledError, Limits, TimeoutError,
+    };
+
+    fn spawn(docker: &FakeDocker, build_options: BuildOptions) -> BuildHandle {
+        let (docker_cmd, spec) = docker.docker_run_cmd(&build_options);
+        super::super::spawn_container(
+            docker.meta(),
+            docker.workdir.clone(),
+            build_options,
+            docker_cmd,
+            spec,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_cancel() {
+        let docker = FakeDocker::new("exec sleep 30");
+        let handle = spawn(&docker, BuildOptions::default());
+        let container_name = handle.container_name().to_string();
+        docker.wait_for_run();
+        handle.canceller().cancel();
+        let err = handle.wait().expect_err("build cancelled");
+        assert!(err.downcast_ref::<CancelledError>().is_some());
+        assert_eq!(docker.calls(), fake_docker::killed(&container_name));
+
+        // a build, which has exited on its own before cancellation, isn't reported as cancelled
+        let docker = FakeDocker::new("exit 3");
+        let handle = spawn(&docker, BuildOptions::default());
+        docker.wait_for_run();
+        std::thread::sleep(Duration::from_millis(300));
+        handle.canceller().cancel();
+        let err = handle.wait().expect_err("build failed");
+        let err = err
+            .downcast_ref::<BuildFailedError>()
+            .expect("a build failure");
+        assert_eq!(err.exit_code, Some(3));
+        assert_eq!(docker.calls(), vec!["run".to_string()]);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let docker = FakeDocker::new("exec sleep 30");
+        let handle = spawn(
+            &docker,
+            BuildOptions {
+                limits: Limits {
+                    timeout: Some(Duration::from_millis(300)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+        let container_name = handle.container_name().to_string();
+        let err = handle.wait().expect_err("build timed out");
+        assert!(err.downcast_ref::<TimeoutError>().is_some());
+        assert_eq!(docker.calls(), fake_docker::killed(&container_name));
+    }
+
+    #[test]
+    fn test_drop() {
+        let docker = FakeDocker::new("exec sleep 30");
+        let handle = spawn(&docker, BuildOptions::default());
+        let container_name = handle.container_name().to_string();
+        docker.wait_for_run();
+        drop(handle);
+        assert_eq!(docker.calls(), fake_docker::killed(&container_name));
+    }
+}
@@ -0,0 +1,335 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(target_os = "linux")]
+use nix::unistd::{getgid, getuid};
+
+use crate::logic::events::SharedObserver;
+use crate::types::report::{DaemonReport, LogLine};
+
+use super::cargo_cache::CargoCache;
+
+/// Options of [super::run], which aren't part of [ContractSourceMetadata](crate::types::contract_source_metadata::ContractSourceMetadata)
+#[derive(Debug, Clone, Default)]
+pub struct BuildOptions {
+    /// User, which build command is run as in container
+    pub user: UserMapping,
+    /// Additional environment variables of build container
+    ///
+    /// Overriding `NEP330_*` variables of [ContractSourceMetadata](crate::types::contract_source_metadata::ContractSourceMetadata) is an error
+    pub env: Vec<(String, String)>,
+    /// Additional host paths, mounted read-only into build container
+    pub mounts: Vec<Mount>,
+    /// Network of build container, passed as `--network`, e.g. `none`
+    pub network: Option<String>,
+    /// Prefix of generated container name, `<prefix>-<timestamp>-<pid>`,
+    /// [DEFAULT_CONTAINER_NAME_PREFIX](Self::DEFAULT_CONTAINER_NAME_PREFIX) if [Option::None]
+    pub container_name_prefix: Option<String>,
+    /// Labels of build container, passed as `--label <key>=<value>`
+    pub labels: BTreeMap<String, String>,
+    /// When image is pulled by `docker run`, passed as `--pull`
+    pub pull_policy: PullPolicy,
+    /// Persistent cargo registry cache, shared between builds
+    pub cargo_cache: Option<CargoCache>,
+    /// Local image to run build in instead of `build_environment`, e.g. [ImageReport::id](crate::types::report::ImageReport::id)
+    /// of an image, loaded with [load_image::check](crate::logic::docker_checks::load_image::check)
+    ///
+    /// `build_environment` is still passed to build as `NEP330_BUILD_INFO_BUILD_ENVIRONMENT`
+    pub image: Option<String>,
+    /// Platform of build container, passed as `--platform`, e.g. `linux/amd64`
+    ///
+    /// When it differs from the platform of docker daemon's host, build is run under emulation,
+    /// see [platform::check](crate::logic::docker_checks::platform::check)
+    pub platform: Option<String>,
+    /// Resource limits of build container and timeout of build
+    pub limits: Limits,
+    /// Where output of build container goes
+    pub output: OutputMode,
+    /// Allocate a pseudo-TTY and keep stdin open, `-it` of `docker run`
+    ///
+    /// Off by default, so that a build never depends on whether current process has a terminal.
+    /// Only applies to [OutputMode::Inherit], CLIs may enable it when stdin is a terminal
+    pub tty: bool,
+    /// Raw arguments, passed to `docker run` right before the name of image
+    ///
+    /// These aren't checked in any way and can silently override any of the above,
+    /// including `-u`, `--volume` and `--workdir` of build container
+    pub unsafe_raw_docker_args: Vec<String>,
+    /// Receiver of [Event](crate::logic::events::Event)s of build, [Silent](crate::logic::events::Silent) by default
+    pub observer: SharedObserver,
+}
+
+impl BuildOptions {
+    pub const DEFAULT_CONTAINER_NAME_PREFIX: &str = "near-verify-rs";
+}
+
+/// User of build container
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UserMapping {
+    /// Ids of current user on Linux, `1000:1000` elsewhere, passed as `-u <uid>:<gid>`
+    ///
+    /// On Linux the volume is mounted natively, and thus the unprivileged user inside Docker container
+    /// should be able to write to the mounted folder that has the host user permissions,
+    /// not specifying this mapping results in UID=Docker-User owned files created in host system
+    #[default]
+    Host,
+    /// User of image, `-u` isn't passed
+    ImageDefault,
+    /// Passed as `-u <uid>:<gid>`
+    ///
+    /// With rootless docker `0:0` in container is mapped to the user running the daemon,
+    /// see [UserMapping::for_daemon]
+    Fixed { uid: u32, gid: u32 },
+    /// Rootless Podman, `--userns keep-id` maps current user to the same ids in container
+    KeepId,
+}
+
+impl UserMapping {
+    /// [UserMapping::Fixed] `0:0` for a rootless daemon, as its container root is the host user,
+    /// and [UserMapping::Host] otherwise
+    pub fn for_daemon(daemon_report: &DaemonReport) -> Self {
+        if daemon_report.rootless {
+            Self::Fixed { uid: 0, gid: 0 }
+        } else {
+            Self::Host
+        }
+    }
+
+    /// `<uid>:<gid>` to be passed as `-u`, [Option::None] for image's default user
+    pub fn docker_user(&self) -> Option<String> {
+        match self {
+            #[cfg(target_os = "linux")]
+            Self::Host => Some(format!("{}:{}", getuid(), getgid())),
+            #[cfg(not(target_os = "linux"))]
+            Self::Host => Some("1000:1000".to_string()),
+            Self::Fixed { uid, gid } => Some(format!("{}:{}", uid, gid)),
+            Self::ImageDefault | Self::KeepId => None,
+        }
+    }
+
+    /// User namespace mode to be passed as `--userns`
+    pub fn userns(&self) -> Option<&'static str> {
+        match self {
+            Self::KeepId => Some("keep-id"),
+            Self::Host | Self::ImageDefault | Self::Fixed { .. } => None,
+        }
+    }
+}
+
+/// Read-only bind mount of a host path into build container
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mount {
+    pub host: camino::Utf8PathBuf,
+    /// Absolute path in container, outside of [NEP330_REPO_MOUNT](crate::logic::NEP330_REPO_MOUNT)
+    pub container: String,
+}
+
+impl Mount {
+    /// `<host>:<container>:ro`
+    pub fn volume_arg(&self) -> eyre::Result<String> {
+        let container = unix_path::Path::new(&self.container);
+        if !container.is_absolute() {
+            return Err(eyre::eyre!(
+                "container path `{}` of mount should be absolute",
+                self.container
+            ));
+        }
+        if container.starts_with(crate::logic::NEP330_REPO_MOUNT)
+            || unix_path::Path::new(crate::logic::NEP330_REPO_MOUNT).starts_with(container)
+        {
+            return Err(eyre::eyre!(
+                "container path `{}` of mount overlaps with source code mount `{}`",
+                self.container,
+                crate::logic::NEP330_REPO_MOUNT
+            ));
+        }
+        Ok(format!("{}:{}:ro", self.host, self.container))
+    }
+}
+
+/// `--pull` policy of `docker run`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PullPolicy {
+    /// Pull image if it's not present locally, default of docker, `--pull` isn't passed
+    #[default]
+    Missing,
+    Always,
+    /// Fail if image isn't present locally, e.g. it's been pulled or loaded and verified beforehand
+    Never,
+}
+
+impl PullPolicy {
+    pub fn docker_arg(&self) -> Option<&'static str> {
+        match self {
+            Self::Missing => None,
+            Self::Always => Some("always"),
+            Self::Never => Some("never"),
+        }
+    }
+}
+
+/// Destination of build container's stdout and stderr
+#[derive(Debug, Clone, Default)]
+pub enum OutputMode {
+    /// Output goes to stdout and stderr of current process
+    #[default]
+    Inherit,
+    /// Output is captured into [BuildLog](crate::types::report::BuildLog) of
+    /// [BuildReport](crate::types::report::BuildReport) and build errors
+    Capture(CaptureOptions),
+}
+
+/// Receiver of build container's output lines as they arrive
+pub trait LogSink: Send + Sync {
+    fn line(&self, line: &LogLine);
+}
+
+impl<F> LogSink for F
+where
+    F: Fn(&LogLine) + Send + Sync,
+{
+    fn line(&self, line: &LogLine) {
+        self(line)
+    }
+}
+
+#[derive(Clone)]
+pub struct CaptureOptions {
+    /// Size limit of captured log, only the last lines, which fit into it, are kept
+    ///
+    /// Longer lines are cut to this size while they're read
+    pub max_bytes: usize,
+    /// Optional receiver of every line, irrespective of total size of log
+    pub sink: Option<Arc<dyn LogSink>>,
+}
+
+impl CaptureOptions {
+    pub const DEFAULT_MAX_BYTES: usize = 4 * 1024 * 1024;
+}
+
+impl Default for CaptureOptions {
+    fn default() -> Self {
+        Self {
+            max_bytes: Self::DEFAULT_MAX_BYTES,
+            sink: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for CaptureOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CaptureOptions")
+            .field("max_bytes", &self.max_bytes)
+            .field("sink", &self.sink.as_ref().map(|_| "<dyn LogSink>"))
+            .finish()
+    }
+}
+
+/// Resource limits, passed to container runtime, and wall-clock timeout of build
+///
+/// All fields are optional, [Option::None] means no limit is applied
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+    /// Number of CPUs, passed as `--cpus`
+    ///
+    /// ## Examples:
+    ///
+    /// ```rust,no_run
+    /// # let cpus: Option<String> =
+    /// Some("1.5".into())
+    /// # ;
+    /// ```
+    pub cpus: Option<String>,
+    /// Memory limit, passed as `--memory`
+    ///
+    /// ## Examples:
+    ///
+    /// ```rust,no_run
+    /// # let memory: Option<String> =
+    /// Some("4g".into())
+    /// # ;
+    /// ```
+    pub memory: Option<String>,
+    /// Maximum number of processes in container, passed as `--pids-limit`
+    pub pids: Option<u32>,
+    /// Size of container's writable layer, passed as `--storage-opt size=<disk>`
+    ///
+    /// Only some storage drivers support this option (e.g. `overlay2` on `xfs` with `pquota`)
+    ///
+    /// ## Examples:
+    ///
+    /// ```rust,no_run
+    /// # let disk: Option<String> =
+    /// Some("20G".into())
+    /// # ;
+    /// ```
+    pub disk: Option<String>,
+    /// Wall-clock timeout, after which build container is killed and removed
+    pub timeout: Option<Duration>,
+}
+
+impl Limits {
+    pub fn docker_args(&self) -> Vec<String> {
+        let mut result = vec![];
+        if let Some(ref cpus) = self.cpus {
+            result.extend(["--cpus".to_string(), cpus.clone()]);
+        }
+        if let Some(ref memory) = self.memory {
+            result.extend(["--memory".to_string(), memory.clone()]);
+        }
+        if let Some(pids) = self.pids {
+            result.extend(["--pids-limit".to_string(), pids.to_string()]);
+        }
+        if let Some(ref disk) = self.disk {
+            result.extend(["--storage-opt".to_string(), format!("size={}", disk)]);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Limits, Mount};
+
+    #[test]
+    fn test_limits_docker_args() {
+        assert!(Limits::default().docker_args().is_empty());
+
+        let limits = Limits {
+            cpus: Some("2".into()),
+            memory: Some("4g".into()),
+            pids: Some(512),
+            disk: Some("20G".into()),
+            timeout: Some(std::time::Duration::from_secs(10)),
+        };
+        assert_eq!(
+            limits.docker_args(),
+            vec![
+                "--cpus",
+                "2",
+                "--memory",
+                "4g",
+                "--pids-limit",
+                "512",
+                "--storage-opt",
+                "size=20G"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decline_mount_overlapping_source_code() {
+        let mount = |container: &str| Mount {
+            host: "/home/user/.cargo/registry".into(),
+            container: container.to_string(),
+        };
+        assert_eq!(
+            mount("/home/near/.cargo/registry").volume_arg().unwrap(),
+            "/home/user/.cargo/registry:/home/near/.cargo/registry:ro"
+        );
+        assert!(mount("/home/near/code/target").volume_arg().is_err());
+        assert!(mount("/home").volume_arg().is_err());
+        assert!(mount("relative").volume_arg().is_err());
+    }
+}
@@ -3,6 +3,7 @@ mod rust_legacy {
 
     use eyre::Context;
 
+    use crate::logic::events::Observer;
     use crate::types::{
         contract_source_metadata::ContractSourceMetadata,
         internal::legacy_rust::{
@@ -45,18 +46,37 @@ mod rust_legacy {
     pub fn wasm_output_path(
         contract_source_metadata: ContractSourceMetadata,
         contract_source_workdir: camino::Utf8PathBuf,
+        observer: &dyn Observer,
     ) -> eyre::Result<camino::Utf8PathBuf> {
+        let build_command = contract_source_metadata
+            .build_info
+            .as_ref()
+            .expect("cannot be [Option::None] as per [ContractSourceMetadata::validate_meta] check")
+            .build_command
+            .clone();
+        let _span = tracing::info_span!(
+            "locate",
+            contract_path = contract_source_metadata
+                .build_info
+                .as_ref()
+                .map(|build_info| build_info.contract_path.as_str()),
+        )
+        .entered();
         let manifest_path = {
-            let manifest_path = manifest_path(contract_source_metadata, contract_source_workdir);
+            let manifest_path =
+                manifest_path(contract_source_metadata, contract_source_workdir.clone());
             ManifestPath::try_from(manifest_path).wrap_err("Assumption about compiling a rust crate in docker container is invalid: manifest file not found")?
         };
 
-        let crate_metadata = CrateMetadata::collect(manifest_path, false)?;
+        let crate_metadata =
+            CrateMetadata::collect(manifest_path, &contract_source_workdir, &build_command)?;
 
         let path = crate_metadata.get_legacy_cargo_near_output_path()?;
+        // artifact isn't found with obscure errors in a directory, owned by container's user
+        #[cfg(target_os = "linux")]
+        super::super::ownership::check(&contract_source_workdir, &path, observer)?;
         tracing::info!(
             target: "near_teach_me",
-            parent: &tracing::Span::none(),
             "assumed artifact result path for a rust crate docker build: `{}`", path
         );
         if !path.exists() {
@@ -0,0 +1,141 @@
+use std::os::unix::fs::MetadataExt;
+
+use super::OwnershipError;
+use crate::logic::events::{self, Event, Observer};
+use camino::Utf8Path;
+
+/// Checks that directories between `workdir` and `artifact`, and `artifact` itself,
+/// can be accessed by current user, before the artifact is looked for on host
+///
+/// Symlinks aren't followed and only regular files are opened, so that a FIFO or a link
+/// in the build's output can't make the check hang or reach outside of `workdir`.
+/// Files, which are accessible but owned by another user, are reported as a warning
+pub(super) fn check(
+    workdir: &Utf8Path,
+    artifact: &Utf8Path,
+    observer: &dyn Observer,
+) -> eyre::Result<()> {
+    let current_uid = nix::unistd::getuid().as_raw();
+    let mut foreign_owner = None;
+    // `artifact` is resolved against canonical source root
+    let workdir = workdir.canonicalize_utf8()?;
+    let mut paths = artifact
+        .ancestors()
+        .take_while(|path| path.starts_with(&workdir) && *path != workdir)
+        .collect::<Vec<_>>();
+    paths.reverse();
+    for path in paths {
+        let metadata = match path.symlink_metadata() {
+            Ok(metadata) => metadata,
+            // missing artifact is reported when it's looked for
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => break,
+            Err(err) => return Err(err.into()),
+        };
+        check_path(path, &metadata, current_uid, &mut foreign_owner)?;
+        if metadata.file_type().is_symlink() {
+            break;
+        }
+    }
+    if let Some((path, owner_uid)) = foreign_owner {
+        tracing::warn!(
+            "build artifact `{}` is owned by uid {}, current uid is {}",
+            path,
+            owner_uid,
+            current_uid
+        );
+        events::emit(
+            observer,
+            Event::Warning {
+                message: format!(
+                    "Build artifact `{}` is owned by uid {}, not by current user (uid {}), \
+                    check container user mapping of build.",
+                    path, owner_uid, current_uid
+                ),
+            },
+        );
+    }
+    Ok(())
+}
+
+fn check_path(
+    path: &Utf8Path,
+    metadata: &std::fs::Metadata,
+    current_uid: u32,
+    foreign_owner: &mut Option<(camino::Utf8PathBuf, u32)>,
+) -> eyre::Result<()> {
+    let owner_uid = metadata.uid();
+    let file_type = metadata.file_type();
+    let access = if file_type.is_dir() {
+        path.read_dir_utf8().map(|_| ())
+    } else if file_type.is_file() {
+        std::fs::File::open(path).map(|_| ())
+    } else {
+        // symlinks and special files aren't opened, only their owner is checked
+        Ok(())
+    };
+    match access {
+        Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => {
+            return Err(eyre::Report::new(OwnershipError {
+                path: path.to_path_buf(),
+                owner_uid,
+                current_uid,
+            }));
+        }
+        Err(err) => return Err(err.into()),
+        Ok(()) => {}
+    }
+    if owner_uid != current_uid && foreign_owner.is_none() {
+        *foreign_owner = Some((path.to_path_buf(), owner_uid));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::OwnershipError;
+    use crate::logic::events::Silent;
+
+    #[test]
+    fn test_unreadable_artifact() {
+        if nix::unistd::getuid().is_root() {
+            // permissions aren't enforced for root
+            return;
+        }
+        let workdir = tempfile::tempdir().unwrap();
+        let workdir =
+            camino::Utf8PathBuf::from_path_buf(workdir.path().canonicalize().unwrap()).unwrap();
+        let near_dir = workdir.join("target/near");
+        std::fs::create_dir_all(&near_dir).unwrap();
+        let artifact = near_dir.join("contract.wasm");
+        std::fs::write(&artifact, b"\0asm").unwrap();
+        super::check(&workdir, &artifact, &Silent).expect("no error");
+
+        std::fs::set_permissions(&artifact, std::fs::Permissions::from_mode(0o000)).unwrap();
+        let err = super::check(&workdir, &artifact, &Silent).expect_err("unreadable");
+        assert_eq!(
+            err.downcast_ref::<OwnershipError>()
+                .expect("ownership error")
+                .path,
+            artifact
+        );
+    }
+
+    #[test]
+    fn test_special_files_not_opened() {
+        let workdir = tempfile::tempdir().unwrap();
+        let workdir =
+            camino::Utf8PathBuf::from_path_buf(workdir.path().canonicalize().unwrap()).unwrap();
+        let near_dir = workdir.join("target/near");
+        std::fs::create_dir_all(&near_dir).unwrap();
+        let fifo = near_dir.join("contract.wasm");
+        let status = std::process::Command::new("mkfifo")
+            .arg(&fifo)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        // opening a FIFO without a writer would block
+        super::check(&workdir, &fifo, &Silent).expect("no error");
+    }
+}
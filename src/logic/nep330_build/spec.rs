@@ -0,0 +1,323 @@
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::logic::events::Stage;
+use crate::types::contract_source_metadata::ContractSourceMetadata;
+use crate::types::internal::container_paths;
+
+use super::cargo_cache::PreparedCargoCache;
+use super::{BuildOptions, Limits, OutputMode};
+
+const NEP330_ENV_PREFIX: &str = "NEP330_";
+
+/// Names of docker containers are `[a-zA-Z0-9][a-zA-Z0-9_.-]+`
+const CONTAINER_NAME_PREFIX_REGEX_PATTERN: &str = r"^[a-zA-Z0-9][a-zA-Z0-9_.-]*$";
+
+/// Configuration of build container, independent of how it's run
+/// (`docker` CLI or Docker Engine API)
+#[derive(Debug, Clone)]
+pub(crate) struct ContainerSpec {
+    /// `<prefix>-<timestamp>-<pid>`
+    pub name: String,
+    /// `<uid>:<gid>`, [Option::None] for image's default user
+    pub user: Option<String>,
+    /// `--userns` mode, e.g. `keep-id` of Podman
+    pub userns: Option<String>,
+    /// `<host path>:<container path>` bind mount of source code
+    pub volume: String,
+    pub workdir: String,
+    /// Allocate a pseudo-TTY and keep stdin open, `-it`
+    pub tty: bool,
+    pub platform: Option<String>,
+    /// `--pull` policy, [Option::None] for docker's default
+    pub pull: Option<&'static str>,
+    /// `<host path>:<container path>:ro` additional bind mounts
+    pub mounts: Vec<String>,
+    pub cargo_cache: Option<PreparedCargoCache>,
+    pub network: Option<String>,
+    pub labels: BTreeMap<String, String>,
+    /// `KEY=VALUE` environment variables, `NEP330_*` ones followed by additional ones
+    pub env: Vec<String>,
+    pub limits: Limits,
+    pub unsafe_raw_docker_args: Vec<String>,
+    pub image: String,
+    pub cmd: Vec<String>,
+}
+
+impl ContainerSpec {
+    pub fn compute(
+        contract_source_metadata: &ContractSourceMetadata,
+        contract_source_workdir: &camino::Utf8Path,
+        build_options: &BuildOptions,
+    ) -> eyre::Result<Self> {
+        let build_info = contract_source_metadata
+            .build_info
+            .clone()
+            .expect("cannot be [Option::None] as per `validate_meta` check");
+
+        let container_name_prefix = build_options
+            .container_name_prefix
+            .as_deref()
+            .unwrap_or(BuildOptions::DEFAULT_CONTAINER_NAME_PREFIX);
+        let regex = regex::Regex::new(CONTAINER_NAME_PREFIX_REGEX_PATTERN).expect("no error");
+        if !regex.is_match(container_name_prefix) {
+            return Err(eyre::eyre!(
+                "container name prefix `{}` doesn't match `{}`",
+                container_name_prefix,
+                CONTAINER_NAME_PREFIX_REGEX_PATTERN
+            ));
+        }
+        let docker_container_name = {
+            // Cross-platform process ID and timestamp
+            let pid = std::process::id().to_string();
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+                .to_string();
+            format!("{}-{}-{}", container_name_prefix, timestamp, pid)
+        };
+        let container_paths =
+            container_paths::Paths::compute(&build_info, contract_source_workdir.to_path_buf())?;
+
+        let mut env = contract_source_metadata.docker_env_vars();
+        for (key, value) in build_options.env.iter() {
+            if key.is_empty() || key.contains('=') {
+                return Err(eyre::eyre!("invalid environment variable name `{}`", key));
+            }
+            if key.starts_with(NEP330_ENV_PREFIX) {
+                return Err(eyre::eyre!(
+                    "environment variable `{}` would override `{}*` variables of contract source metadata",
+                    key,
+                    NEP330_ENV_PREFIX
+                ));
+            }
+            env.push(format!("{}={}", key, value));
+        }
+        let mounts = build_options
+            .mounts
+            .iter()
+            .map(|mount| mount.volume_arg())
+            .collect::<eyre::Result<Vec<_>>>()?;
+        let cargo_cache = build_options
+            .cargo_cache
+            .as_ref()
+            .map(|cargo_cache| cargo_cache.prepare(&build_info.build_environment))
+            .transpose()?;
+        if let Some(key) = build_options.labels.keys().find(|key| key.is_empty()) {
+            return Err(eyre::eyre!("invalid label name `{}`", key));
+        }
+
+        let shell_escaped_cargo_cmd =
+            crate::logic::shell_escape_nep330_build_command(build_info.build_command);
+
+        let tty = match build_options.output {
+            OutputMode::Inherit => build_options.tty,
+            OutputMode::Capture(_) => false,
+        };
+
+        let image = build_options
+            .image
+            .clone()
+            .unwrap_or(build_info.build_environment);
+
+        let spec = Self {
+            name: docker_container_name,
+            user: build_options.user.docker_user(),
+            userns: build_options.user.userns().map(str::to_string),
+            volume: container_paths.host_volume_arg,
+            workdir: container_paths.crate_path,
+            tty,
+            platform: build_options.platform.clone(),
+            pull: build_options.pull_policy.docker_arg(),
+            mounts,
+            cargo_cache,
+            network: build_options.network.clone(),
+            labels: build_options.labels.clone(),
+            env,
+            limits: build_options.limits.clone(),
+            unsafe_raw_docker_args: build_options.unsafe_raw_docker_args.clone(),
+            image,
+            cmd: vec![
+                "/bin/bash".to_string(),
+                "-c".to_string(),
+                shell_escaped_cargo_cmd,
+            ],
+        };
+        Ok(spec)
+    }
+
+    /// [Stage::Build] of this container, reported once it's about to be run
+    pub fn stage(&self) -> Stage {
+        Stage::Build {
+            container_name: self.name.clone(),
+            // `cmd` is `/bin/bash -c <shell-escaped build command>`
+            command: self.cmd.last().cloned().unwrap_or_default(),
+        }
+    }
+
+    /// Arguments of `docker run`
+    pub fn docker_run_args(&self) -> Vec<String> {
+        let mut docker_args = vec![];
+        if let Some(ref user) = self.user {
+            docker_args.extend(["-u".to_string(), user.clone()]);
+        }
+        if let Some(ref userns) = self.userns {
+            docker_args.extend(["--userns".to_string(), userns.clone()]);
+        }
+        docker_args.extend([
+            "--name".to_string(),
+            self.name.clone(),
+            "--volume".to_string(),
+            self.volume.clone(),
+            "--rm".to_string(),
+            "--workdir".to_string(),
+            self.workdir.clone(),
+        ]);
+        if self.tty {
+            docker_args.push("-it".to_string());
+        }
+        if let Some(ref platform) = self.platform {
+            docker_args.extend(["--platform".to_string(), platform.clone()]);
+        }
+        if let Some(pull) = self.pull {
+            docker_args.extend(["--pull".to_string(), pull.to_string()]);
+        }
+        for mount in self.mounts.iter() {
+            docker_args.extend(["--volume".to_string(), mount.clone()]);
+        }
+        if let Some(ref cargo_cache) = self.cargo_cache {
+            for volume in cargo_cache.volumes.iter() {
+                docker_args.extend(["--volume".to_string(), volume.clone()]);
+            }
+        }
+        if let Some(ref network) = self.network {
+            docker_args.extend(["--network".to_string(), network.clone()]);
+        }
+        for (key, value) in self.labels.iter() {
+            docker_args.extend(["--label".to_string(), format!("{}={}", key, value)]);
+        }
+        for env_var in self.env.iter() {
+            docker_args.extend(["--env".to_string(), env_var.clone()]);
+        }
+        docker_args.extend(self.limits.docker_args());
+        docker_args.extend(self.unsafe_raw_docker_args.iter().cloned());
+        docker_args.push(self.image.clone());
+        docker_args.extend(self.cmd.iter().cloned());
+        docker_args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ContainerSpec;
+    use crate::logic::nep330_build::{
+        BuildOptions, CaptureOptions, OutputMode, PullPolicy, UserMapping,
+    };
+    use crate::types::contract_source_metadata::ContractSourceMetadata;
+
+    fn meta() -> ContractSourceMetadata {
+        serde_json::from_str(
+            r#"{
+  "build_info": {
+    "build_command": ["cargo", "near", "build", "non-reproducible-wasm", "--locked"],
+    "build_environment": "sourcescan/cargo-near:0.13.4-rust-1.85.0@sha256:a9d8bee7b134856cc8baa142494a177f2ba9ecfededfcdd38f634e14cca8aae2",
+    "contract_path": "contract",
+    "source_code_snapshot": "git+https://github.com/dj8yfo/verify_contracts_collection?rev=e3303f0cf8761b99c84ec40a4b7e2a3b3e3b5e3c"
+  },
+  "link": null,
+  "standards": [],
+  "version": "0.1.0"
+}"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_tty_is_explicit() {
+        let workdir = camino::Utf8Path::new("/tmp/repo");
+        let spec = ContainerSpec::compute(&meta(), workdir, &BuildOptions::default()).unwrap();
+        assert!(!spec.docker_run_args().contains(&"-it".to_string()));
+
+        let build_options = BuildOptions {
+            tty: true,
+            ..Default::default()
+        };
+        let spec = ContainerSpec::compute(&meta(), workdir, &build_options).unwrap();
+        let args = spec.docker_run_args();
+        assert!(args.contains(&"-it".to_string()));
+        assert_eq!(spec.workdir, "/home/near/code/contract");
+        assert_eq!(
+            &args[args.len() - 4..],
+            [
+                "sourcescan/cargo-near:0.13.4-rust-1.85.0@sha256:a9d8bee7b134856cc8baa142494a177f2ba9ecfededfcdd38f634e14cca8aae2",
+                "/bin/bash",
+                "-c",
+                "cargo near build non-reproducible-wasm --locked"
+            ]
+        );
+
+        let build_options = BuildOptions {
+            tty: true,
+            output: OutputMode::Capture(CaptureOptions::default()),
+            ..Default::default()
+        };
+        let spec = ContainerSpec::compute(&meta(), workdir, &build_options).unwrap();
+        assert!(!spec.tty);
+    }
+
+    #[test]
+    fn test_typed_options() {
+        let workdir = camino::Utf8Path::new("/tmp/repo");
+        let build_options = BuildOptions {
+            user: UserMapping::Fixed {
+                uid: 1001,
+                gid: 1002,
+            },
+            env: vec![("RUSTFLAGS".into(), "-Dwarnings".into())],
+            network: Some("none".into()),
+            container_name_prefix: Some("verifier".into()),
+            labels: [("job".to_string(), "42".to_string())].into(),
+            pull_policy: PullPolicy::Never,
+            ..Default::default()
+        };
+        let spec = ContainerSpec::compute(&meta(), workdir, &build_options).unwrap();
+        assert!(spec.name.starts_with("verifier-"));
+        let args = spec.docker_run_args();
+        let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+        assert_eq!(&args[..2], ["-u", "1001:1002"]);
+        for expected in [
+            ["--pull", "never"],
+            ["--network", "none"],
+            ["--label", "job=42"],
+            ["--env", "RUSTFLAGS=-Dwarnings"],
+        ] {
+            assert!(
+                args.windows(2).any(|pair| pair == expected),
+                "{:?}",
+                expected
+            );
+        }
+
+        let keep_id = BuildOptions {
+            user: UserMapping::KeepId,
+            ..Default::default()
+        };
+        let args = ContainerSpec::compute(&meta(), workdir, &keep_id)
+            .unwrap()
+            .docker_run_args();
+        assert_eq!(&args[..2], ["--userns", "keep-id"]);
+        assert!(!args.contains(&"-u".to_string()));
+
+        let override_nep330_env = BuildOptions {
+            env: vec![("NEP330_BUILD_INFO_CONTRACT_PATH".into(), "other".into())],
+            ..Default::default()
+        };
+        assert!(ContainerSpec::compute(&meta(), workdir, &override_nep330_env).is_err());
+        let invalid_prefix = BuildOptions {
+            container_name_prefix: Some("-rm".into()),
+            ..Default::default()
+        };
+        assert!(ContainerSpec::compute(&meta(), workdir, &invalid_prefix).is_err());
+    }
+}
@@ -0,0 +1,28 @@
+use crate::logic::docker_checks::ImageProvider;
+use crate::logic::events::SharedObserver;
+use crate::types::report::ImageReport;
+
+/// Async variant of [ImageProvider::check]
+///
+/// Verification of an image archive reads all of its blobs, so it's run on a blocking thread
+pub async fn check(
+    image_provider: &ImageProvider,
+    docker_image: &str,
+    platform: Option<&str>,
+    observer: &SharedObserver,
+) -> eyre::Result<ImageReport> {
+    match image_provider {
+        ImageProvider::Registry => {
+            super::pull_image::check_platform(docker_image, platform, observer).await
+        }
+        ImageProvider::Archive(_) => {
+            let image_provider = image_provider.clone();
+            let docker_image = docker_image.to_string();
+            let observer = observer.clone();
+            tokio::task::spawn_blocking(move || {
+                image_provider.check(&docker_image, None, &observer)
+            })
+            .await?
+        }
+    }
+}
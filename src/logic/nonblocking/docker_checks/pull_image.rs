@@ -0,0 +1,50 @@
+use crate::logic::docker_checks::pull_image;
+use crate::logic::events::{self, Event, Observer, SharedObserver, Stage};
+use crate::types::report::ImageReport;
+
+/// Async variant of [pull_image::check]
+pub async fn check(docker_image: &str, observer: &SharedObserver) -> eyre::Result<ImageReport> {
+    check_platform(docker_image, None, observer).await
+}
+
+/// Async variant of [pull_image::check_platform]
+pub async fn check_platform(
+    docker_image: &str,
+    platform: Option<&str>,
+    observer: &SharedObserver,
+) -> eyre::Result<ImageReport> {
+    let stage = Stage::PullImage {
+        image: docker_image.to_string(),
+    };
+    events::stage_async(observer, stage, async {
+        let image = pull(docker_image, platform, observer).await?;
+        events::emit(
+            observer,
+            Event::ImagePulled {
+                image: image.clone(),
+            },
+        );
+        Ok(image)
+    })
+    .await
+}
+
+async fn pull(
+    docker_image: &str,
+    platform: Option<&str>,
+    observer: &dyn Observer,
+) -> eyre::Result<ImageReport> {
+    let mut docker_cmd =
+        tokio::process::Command::from(pull_image::docker_pull_cmd(docker_image, platform));
+    docker_cmd.kill_on_drop(true);
+
+    let status_result = docker_cmd.status().await;
+    pull_image::handle_status(docker_image, docker_cmd.as_std(), status_result, observer)?;
+
+    let mut docker_cmd =
+        tokio::process::Command::from(pull_image::docker_inspect_cmd(docker_image));
+    docker_cmd.kill_on_drop(true);
+    let output_result = docker_cmd.output().await;
+    pull_image::parse_inspect_output(docker_image, docker_cmd.as_std(), output_result, observer)?
+        .verify(docker_image)
+}
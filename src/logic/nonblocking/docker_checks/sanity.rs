@@ -0,0 +1,25 @@
+use crate::logic::docker_checks::sanity;
+use crate::logic::events::{self, SharedObserver, Stage};
+use crate::types::report::DaemonReport;
+
+/// Async variant of [sanity::check]
+pub async fn check(observer: &SharedObserver) -> eyre::Result<()> {
+    events::stage_async(observer, Stage::DockerCheck, async {
+        let mut docker_cmd = tokio::process::Command::from(sanity::docker_hello_world_cmd());
+        docker_cmd.kill_on_drop(true);
+        let output_result = docker_cmd.output().await;
+        sanity::handle_output(docker_cmd.as_std(), output_result, observer)
+    })
+    .await
+}
+
+/// Async variant of [sanity::check_daemon]
+pub async fn check_daemon(observer: &SharedObserver) -> eyre::Result<DaemonReport> {
+    events::stage_async(observer, Stage::DockerCheck, async {
+        let mut docker_cmd = tokio::process::Command::from(sanity::docker_info_cmd());
+        docker_cmd.kill_on_drop(true);
+        let output_result = docker_cmd.output().await;
+        sanity::handle_info_output(docker_cmd.as_std(), output_result, observer)
+    })
+    .await
+}
@@ -0,0 +1,354 @@
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::task::JoinHandle;
+use tracing::Instrument;
+
+use crate::logic::events::{self, Event, SharedObserver};
+use crate::logic::internal::docker_command;
+use crate::logic::nep330_build::{
+    self, capture, BuildOptions, CaptureOptions, ContainerSpec, ExitedRun, OutputMode,
+    TimeoutError, ERR_REPRODUCIBLE,
+};
+use crate::types::contract_source_metadata::ContractSourceMetadata;
+use crate::types::report::{BuildLog, BuildReport, Stream};
+
+/// Async variant of [nep330_build::run]
+///
+/// Dropping the returned future before completion kills `docker run` process,
+/// and kills and removes build container
+pub async fn run(
+    contract_source_metadata: ContractSourceMetadata,
+    contract_source_workdir: camino::Utf8PathBuf,
+    build_options: BuildOptions,
+) -> eyre::Result<BuildReport> {
+    let (docker_cmd, spec) = nep330_build::docker_run_cmd(
+        &contract_source_metadata,
+        &contract_source_workdir,
+        &build_options,
+    )?;
+    let stage = spec.stage();
+    let span = stage.span();
+    async move {
+        let observer = build_options.observer.clone();
+        events::emit(
+            &observer,
+            Event::StageStarted {
+                stage: stage.clone(),
+            },
+        );
+        let result = run_container(
+            contract_source_metadata,
+            contract_source_workdir,
+            build_options,
+            docker_cmd,
+            spec,
+        )
+        .await;
+        events::emit(
+            &observer,
+            Event::StageFinished {
+                stage,
+                success: result.is_ok(),
+            },
+        );
+        result
+    }
+    .instrument(span)
+    .await
+}
+
+async fn run_container(
+    contract_source_metadata: ContractSourceMetadata,
+    contract_source_workdir: camino::Utf8PathBuf,
+    build_options: BuildOptions,
+    docker_cmd: std::process::Command,
+    spec: ContainerSpec,
+) -> eyre::Result<BuildReport> {
+    let docker_container_name = spec.name;
+    let observer = build_options.observer;
+    let docker_command = docker_command::command_args(&docker_cmd);
+    let mut docker_cmd = tokio::process::Command::from(docker_cmd);
+    docker_cmd.kill_on_drop(true);
+
+    let mut container_guard = ContainerGuard {
+        docker: docker_cmd.as_std().get_program().to_owned(),
+        container_name: Some(docker_container_name.clone()),
+    };
+    let child_result = docker_cmd.spawn();
+    let mut child = docker_command::handle_io_error(
+        docker_cmd.as_std(),
+        child_result,
+        eyre::eyre!(ERR_REPRODUCIBLE),
+        &observer,
+    )?;
+    events::emit(
+        &observer,
+        Event::ContainerStarted {
+            container_name: docker_container_name.clone(),
+            docker_command: docker_command.clone(),
+        },
+    );
+
+    let capture = match build_options.output {
+        OutputMode::Inherit => None,
+        OutputMode::Capture(capture_options) => Some(Capture::start(
+            &mut child,
+            &docker_container_name,
+            capture_options,
+            &observer,
+        )),
+    };
+
+    let status = match build_options.limits.timeout {
+        None => child.wait().await?,
+        Some(timeout) => match tokio::time::timeout(timeout, child.wait()).await {
+            Ok(status_result) => status_result?,
+            Err(_elapsed) => {
+                tracing::warn!(
+                    "build container `{}` exceeded timeout of {:?}, killing it",
+                    docker_container_name,
+                    timeout
+                );
+                container_guard.kill_and_remove().await;
+                // `docker run` client process normally exits after its container is killed,
+                // this is for the case it doesn't
+                let _ = child.kill().await;
+                return Err(eyre::Report::new(TimeoutError {
+                    container_name: docker_container_name,
+                    timeout,
+                    log: Capture::finish_optional(capture).await,
+                }));
+            }
+        },
+    };
+    container_guard.disarm();
+    let log = Capture::finish_optional(capture).await;
+
+    // resolving output path of legacy rust crates reads and parses `Cargo.toml` and
+    // `.cargo/config.toml` files, and checks ownership of artifact path
+    tokio::task::spawn_blocking(move || {
+        nep330_build::handle_docker_run_status(
+            contract_source_metadata,
+            contract_source_workdir,
+            ExitedRun {
+                status,
+                docker_command,
+                container_name: docker_container_name,
+                log,
+                cargo_cache: spec.cargo_cache,
+            },
+            &observer,
+        )
+    })
+    .await?
+}
+
+/// Kills and removes build container when dropped, unless disarmed
+struct ContainerGuard {
+    docker: std::ffi::OsString,
+    container_name: Option<String>,
+}
+
+impl ContainerGuard {
+    fn disarm(&mut self) {
+        self.container_name = None;
+    }
+
+    async fn kill_and_remove(&mut self) {
+        if let Some(container_name) = self.container_name.take() {
+            let docker = self.docker.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                docker_command::container::kill_and_remove(&docker, &container_name)
+            })
+            .await;
+            if let Err(err) = result {
+                tracing::warn!("removing build container failed: {:?}", err);
+            }
+        }
+    }
+}
+
+impl Drop for ContainerGuard {
+    fn drop(&mut self) {
+        if let Some(container_name) = self.container_name.take() {
+            tracing::debug!(
+                "build future dropped before completion, removing container `{}`",
+                container_name
+            );
+            // not blocking an executor's thread on `docker kill` and `docker rm`
+            let docker = self.docker.clone();
+            std::thread::spawn(move || {
+                docker_command::container::kill_and_remove(&docker, &container_name)
+            });
+        }
+    }
+}
+
+/// Async counterpart of [capture::Capture]
+struct Capture {
+    log: Arc<Mutex<BuildLog>>,
+    readers: Vec<JoinHandle<()>>,
+}
+
+impl Capture {
+    fn start(
+        child: &mut tokio::process::Child,
+        container_name: &str,
+        options: CaptureOptions,
+        observer: &SharedObserver,
+    ) -> Self {
+        let log = Arc::new(Mutex::new(BuildLog::default()));
+        let mut readers = vec![];
+        if let Some(stdout) = child.stdout.take() {
+            readers.push(reader_task(
+                stdout,
+                Stream::Stdout,
+                container_name,
+                &log,
+                &options,
+                observer,
+            ));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            readers.push(reader_task(
+                stderr,
+                Stream::Stderr,
+                container_name,
+                &log,
+                &options,
+                observer,
+            ));
+        }
+        Self { log, readers }
+    }
+
+    async fn finish_optional(capture: Option<Self>) -> Option<BuildLog> {
+        let capture = capture?;
+        for reader in capture.readers {
+            if let Err(err) = reader.await {
+                tracing::warn!("build output reader task failed: {:?}", err);
+            }
+        }
+        let mut log = capture.log.lock().expect("reader tasks have finished");
+        Some(std::mem::take(&mut *log))
+    }
+}
+
+fn reader_task<R: AsyncRead + Unpin + Send + 'static>(
+    reader: R,
+    stream: Stream,
+    container_name: &str,
+    log: &Arc<Mutex<BuildLog>>,
+    options: &CaptureOptions,
+    observer: &SharedObserver,
+) -> JoinHandle<()> {
+    let container_name = container_name.to_string();
+    let log = log.clone();
+    let options = options.clone();
+    let observer = observer.clone();
+    tokio::spawn(async move {
+        let mut reader = reader;
+        let mut record = |line: &[u8], truncated: bool| {
+            capture::record_line(
+                line,
+                truncated,
+                stream,
+                &container_name,
+                &log,
+                &options,
+                &observer,
+            )
+        };
+        let mut lines = capture::Lines::new(options.max_bytes);
+        let mut chunk = [0u8; capture::CHUNK_SIZE];
+        loop {
+            let read = match reader.read(&mut chunk).await {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(err) => {
+                    tracing::warn!("error reading build output from {:?}: {:?}", stream, err);
+                    break;
+                }
+            };
+            lines.push(&chunk[..read], &mut record);
+        }
+        lines.finish(record);
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::time::Duration;
+
+    use crate::logic::nep330_build::fake_docker::{self, FakeDocker};
+    use crate::logic::nep330_build::{BuildFailedError, BuildOptions, Limits, TimeoutError};
+    use crate::types::report::BuildReport;
+
+    fn run(
+        docker: &FakeDocker,
+        build_options: BuildOptions,
+    ) -> (
+        impl std::future::Future<Output = eyre::Result<BuildReport>>,
+        String,
+    ) {
+        let (docker_cmd, spec) = docker.docker_run_cmd(&build_options);
+        let container_name = spec.name.clone();
+        let future = super::run_container(
+            docker.meta(),
+            docker.workdir.clone(),
+            build_options,
+            docker_cmd,
+            spec,
+        );
+        (future, container_name)
+    }
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_cancel_on_drop() {
+        let docker = FakeDocker::new("exec sleep 30");
+        let (future, container_name) = run(&docker, BuildOptions::default());
+        let result = runtime()
+            .block_on(async { tokio::time::timeout(Duration::from_millis(300), future).await });
+        assert!(result.is_err(), "build future is dropped on timeout");
+        assert_eq!(
+            docker.wait_for_removal(&container_name),
+            fake_docker::killed(&container_name)
+        );
+    }
+
+    #[test]
+    fn test_container_cleanup() {
+        let docker = FakeDocker::new("exec sleep 30");
+        let (future, container_name) = run(
+            &docker,
+            BuildOptions {
+                limits: Limits {
+                    timeout: Some(Duration::from_millis(300)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+        let err = runtime().block_on(future).expect_err("build timed out");
+        assert!(err.downcast_ref::<TimeoutError>().is_some());
+        assert_eq!(docker.calls(), fake_docker::killed(&container_name));
+
+        // container of a finished build is removed by `--rm`
+        let docker = FakeDocker::new("exit 3");
+        let (future, _) = run(&docker, BuildOptions::default());
+        let err = runtime().block_on(future).expect_err("build failed");
+        let err = err
+            .downcast_ref::<BuildFailedError>()
+            .expect("a build failure");
+        assert_eq!(err.exit_code, Some(3));
+        assert_eq!(docker.calls(), vec!["run".to_string()]);
+    }
+}
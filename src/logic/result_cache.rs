@@ -0,0 +1,425 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+
+use crate::logic::nep330_build::BuildOptions;
+use crate::types::contract_source_metadata::ContractSourceMetadata;
+use crate::types::sha256_checksum::SHA256Checksum;
+use crate::types::whitelist::{self, Whitelist};
+
+/// Version of on-disk format, entries of other versions are ignored and overwritten
+const FORMAT_VERSION: u32 = 1;
+const ENTRY_EXTENSION: &str = "json";
+/// Distinguishes temp files of concurrent writes within a process
+static TMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Local cache of verification results, one json file per entry in a directory
+///
+/// Entries are keyed by [ResultCache::key], a hit means that the same metadata has already been
+/// built with the same whitelist and build-affecting options, and the artifact hash can be reused.
+/// Reads and inserts only touch the entry of their key, expired and excess entries are removed
+/// from disk with [ResultCache::prune]
+#[derive(Debug, Clone)]
+pub struct ResultCache {
+    dir: Utf8PathBuf,
+    /// Entries older than this are treated as missing
+    pub ttl: Option<Duration>,
+    /// Oldest entries are evicted on [ResultCache::prune] to keep at most this many
+    ///
+    /// It's only enforced by [ResultCache::prune], inserts don't evict entries
+    pub max_entries: usize,
+}
+
+/// Entry of [ResultCache], as stored on disk
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedResult {
+    format_version: u32,
+    pub key: String,
+    /// Seconds since unix epoch
+    pub created_at: u64,
+    /// `build_environment` of metadata
+    pub build_environment: String,
+    /// sha256 of resulting wasm, hex
+    pub wasm_hash: String,
+}
+
+impl CachedResult {
+    pub fn checksum(&self) -> eyre::Result<SHA256Checksum> {
+        Ok(SHA256Checksum {
+            hash: hex::decode(&self.wasm_hash)?,
+        })
+    }
+}
+
+impl ResultCache {
+    pub const DEFAULT_MAX_ENTRIES: usize = 10_000;
+
+    /// Opens cache in `dir`, creating it if it doesn't exist
+    pub fn open(dir: impl Into<Utf8PathBuf>) -> eyre::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)
+            .map_err(|err| eyre::eyre!("couldn't create result cache `{}`: {}", dir, err))?;
+        Ok(Self {
+            dir,
+            ttl: None,
+            max_entries: Self::DEFAULT_MAX_ENTRIES,
+        })
+    }
+
+    pub fn dir(&self) -> &Utf8Path {
+        &self.dir
+    }
+
+    /// sha256 hex of canonical json of `contract_source_metadata`, `whitelist`
+    /// and those of `build_options` which can affect resulting artifact
+    ///
+    /// Object keys are sorted, and whitelist entries are sorted too, as their order doesn't
+    /// affect the result of validation
+    pub fn key(
+        contract_source_metadata: &ContractSourceMetadata,
+        whitelist: Option<&Whitelist>,
+        build_options: &BuildOptions,
+    ) -> eyre::Result<String> {
+        let whitelist = whitelist.map(|whitelist| whitelist::sorted(whitelist));
+        let mounts = build_options
+            .mounts
+            .iter()
+            .map(|mount| mount.volume_arg())
+            .collect::<eyre::Result<Vec<_>>>()?;
+        let canonical = serde_json::json!({
+            "format_version": FORMAT_VERSION,
+            "metadata": contract_source_metadata,
+            "whitelist": whitelist,
+            "options": {
+                "image": build_options.image,
+                "platform": build_options.platform,
+                "env": build_options.env,
+                "mounts": mounts,
+                "network": build_options.network,
+                "unsafe_raw_docker_args": build_options.unsafe_raw_docker_args,
+            },
+        });
+        // `serde_json::Value` objects are `BTreeMap`s, so keys are serialized sorted
+        let canonical = serde_json::to_vec(&canonical)?;
+        Ok(hex::encode(<sha2::Sha256 as sha2::Digest>::digest(
+            &canonical,
+        )))
+    }
+
+    /// Returns entry for `key`, if present and not expired
+    ///
+    /// All methods taking a `key` fail on one, which isn't sha256 hex of [ResultCache::key]
+    pub fn get(&self, key: &str) -> eyre::Result<Option<CachedResult>> {
+        let path = self.entry_path(key)?;
+        let content = match std::fs::read(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let entry = match serde_json::from_slice::<CachedResult>(&content) {
+            Ok(entry) if entry.format_version == FORMAT_VERSION && entry.key == key => entry,
+            _ => {
+                tracing::debug!("ignoring malformed result cache entry `{}`", path);
+                return Ok(None);
+            }
+        };
+        if self.expired(&entry) {
+            return Ok(None);
+        }
+        Ok(Some(entry))
+    }
+
+    /// Stores `checksum` of artifact, built from `contract_source_metadata`, under `key`
+    pub fn insert(
+        &self,
+        key: &str,
+        contract_source_metadata: &ContractSourceMetadata,
+        checksum: &SHA256Checksum,
+    ) -> eyre::Result<CachedResult> {
+        let entry = CachedResult {
+            format_version: FORMAT_VERSION,
+            key: key.to_string(),
+            created_at: now(),
+            build_environment: contract_source_metadata
+                .build_info
+                .as_ref()
+                .map(|build_info| build_info.build_environment.clone())
+                .unwrap_or_default(),
+            wasm_hash: checksum.to_hex_string(),
+        };
+        let path = self.entry_path(key)?;
+        // rename is atomic, so readers never see a partially written entry
+        let tmp_path = path.with_extension(format!(
+            "tmp-{}-{}",
+            std::process::id(),
+            TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::write(&tmp_path, serde_json::to_vec_pretty(&entry)?)?;
+        if let Err(err) = std::fs::rename(&tmp_path, &path) {
+            let _ = std::fs::remove_file(&tmp_path);
+            return Err(err.into());
+        }
+        Ok(entry)
+    }
+
+    /// Returns cached checksum for `key` or computes it with `build` and stores it
+    pub fn get_or_insert_with(
+        &self,
+        key: &str,
+        contract_source_metadata: &ContractSourceMetadata,
+        build: impl FnOnce() -> eyre::Result<SHA256Checksum>,
+    ) -> eyre::Result<SHA256Checksum> {
+        if let Some(entry) = self.get(key)? {
+            tracing::info!("result cache hit `{}`", key);
+            return entry.checksum();
+        }
+        let checksum = build()?;
+        self.insert(key, contract_source_metadata, &checksum)?;
+        Ok(checksum)
+    }
+
+    pub fn invalidate(&self, key: &str) -> eyre::Result<()> {
+        match std::fs::remove_file(self.entry_path(key)?) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Removes entries of `build_environment` image, e.g. after it has been found to be compromised
+    pub fn invalidate_image(&self, build_environment: &str) -> eyre::Result<()> {
+        for entry in self.entries()? {
+            if entry.build_environment == build_environment {
+                self.invalidate(&entry.key)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn clear(&self) -> eyre::Result<()> {
+        for entry in self.entries()? {
+            self.invalidate(&entry.key)?;
+        }
+        Ok(())
+    }
+
+    /// Valid and not expired entries, oldest first
+    pub fn entries(&self) -> eyre::Result<Vec<CachedResult>> {
+        let mut entries = vec![];
+        for dir_entry in self.dir.read_dir_utf8()? {
+            let dir_entry = dir_entry?;
+            if dir_entry.path().extension() != Some(ENTRY_EXTENSION) {
+                continue;
+            }
+            let Some(key) = dir_entry.path().file_stem().filter(|key| is_key(key)) else {
+                continue;
+            };
+            if let Some(entry) = self.get(key)? {
+                entries.push(entry);
+            }
+        }
+        entries.sort_by_key(|entry| entry.created_at);
+        Ok(entries)
+    }
+
+    /// Removes expired entries from disk, and oldest ones above [ResultCache::max_entries]
+    pub fn prune(&self) -> eyre::Result<()> {
+        for dir_entry in self.dir.read_dir_utf8()? {
+            let dir_entry = dir_entry?;
+            if dir_entry.path().extension() != Some(ENTRY_EXTENSION) {
+                continue;
+            }
+            let Some(key) = dir_entry.path().file_stem().filter(|key| is_key(key)) else {
+                continue;
+            };
+            let expired = match std::fs::read(dir_entry.path()) {
+                Ok(content) => serde_json::from_slice::<CachedResult>(&content)
+                    .is_ok_and(|entry| self.expired(&entry)),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => false,
+                Err(err) => return Err(err.into()),
+            };
+            if expired {
+                self.invalidate(key)?;
+            }
+        }
+        let entries = self.entries()?;
+        let excess = entries.len().saturating_sub(self.max_entries);
+        for entry in entries.iter().take(excess) {
+            self.invalidate(&entry.key)?;
+        }
+        Ok(())
+    }
+
+    fn expired(&self, entry: &CachedResult) -> bool {
+        match self.ttl {
+            Some(ttl) => now().saturating_sub(entry.created_at) > ttl.as_secs(),
+            None => false,
+        }
+    }
+
+    fn entry_path(&self, key: &str) -> eyre::Result<Utf8PathBuf> {
+        if !is_key(key) {
+            return Err(eyre::eyre!(
+                "invalid result cache key `{}`, expected sha256 hex of `ResultCache::key`",
+                key
+            ));
+        }
+        Ok(self.dir.join(format!("{}.{}", key, ENTRY_EXTENSION)))
+    }
+}
+
+/// Whether `key` has the form of [ResultCache::key], so that its entry can't be outside of cache dir
+fn is_key(key: &str) -> bool {
+    key.len() == 64
+        && key
+            .bytes()
+            .all(|byte| byte.is_ascii_digit() || (b'a'..=b'f').contains(&byte))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::ResultCache;
+    use crate::logic::nep330_build::BuildOptions;
+    use crate::types::contract_source_metadata::ContractSourceMetadata;
+    use crate::types::sha256_checksum::SHA256Checksum;
+    use crate::types::whitelist::WhitelistEntry;
+
+    const META: &str = r#"{
+  "build_info": {
+    "build_command": ["cargo", "near", "build", "non-reproducible-wasm", "--locked"],
+    "build_environment": "sourcescan/cargo-near:0.13.4-rust-1.85.0@sha256:a9d8bee7b134856cc8baa142494a177f2ba9ecfededfcdd38f634e14cca8aae2",
+    "contract_path": "",
+    "source_code_snapshot": "git+https://github.com/dj8yfo/verify_contracts_collection?rev=e3303f0cf8761b99f84f93c3a2d7046be6f4edb5"
+  },
+  "link": null,
+  "standards": [{"standard": "nep330", "version": "1.2.0"}],
+  "version": "1.0.0"
+}"#;
+
+    fn key(byte: u8) -> String {
+        hex::encode([byte; 32])
+    }
+
+    fn open() -> (tempfile::TempDir, ResultCache) {
+        let tempdir = tempfile::tempdir().unwrap();
+        let dir = camino::Utf8PathBuf::from_path_buf(tempdir.path().join("results")).unwrap();
+        (tempdir, ResultCache::open(dir).unwrap())
+    }
+
+    #[test]
+    fn test_key_is_canonical() {
+        let meta: ContractSourceMetadata = serde_json::from_str(META).unwrap();
+        let entry = |image: &str| WhitelistEntry {
+            expected_docker_image: image.into(),
+            expected_command_prefix: vec!["cargo".into(), "near".into()],
+        };
+        let whitelist = vec![entry("sourcescan/cargo-near"), entry("other/cargo-near")];
+        let reversed = whitelist.iter().rev().cloned().collect::<Vec<_>>();
+        let options = BuildOptions::default();
+        let key = ResultCache::key(&meta, Some(&whitelist), &options).unwrap();
+        assert_eq!(
+            key,
+            ResultCache::key(&meta, Some(&reversed), &options).unwrap()
+        );
+        assert_ne!(key, ResultCache::key(&meta, None, &options).unwrap());
+        let with_network = BuildOptions {
+            network: Some("none".into()),
+            ..Default::default()
+        };
+        assert_ne!(
+            key,
+            ResultCache::key(&meta, Some(&whitelist), &with_network).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_get_or_insert_and_bounds() {
+        let (_tempdir, mut cache) = open();
+        let meta: ContractSourceMetadata = serde_json::from_str(META).unwrap();
+        let checksum = || SHA256Checksum {
+            hash: vec![0xab; 32],
+        };
+
+        let built = cache
+            .get_or_insert_with(&key(1), &meta, || Ok(checksum()))
+            .unwrap();
+        let cached = cache
+            .get_or_insert_with(&key(1), &meta, || panic!("shouldn't rebuild on a hit"))
+            .unwrap();
+        assert_eq!(built.hash, cached.hash);
+
+        // survives reopening
+        let reopened = ResultCache::open(cache.dir().to_path_buf()).unwrap();
+        assert!(reopened.get(&key(1)).unwrap().is_some());
+
+        cache.max_entries = 1;
+        cache.insert(&key(2), &meta, &checksum()).unwrap();
+        assert_eq!(cache.entries().unwrap().len(), 2);
+        cache.prune().unwrap();
+        assert_eq!(cache.entries().unwrap().len(), 1);
+
+        cache.invalidate_image("sourcescan/cargo-near:0.13.4-rust-1.85.0@sha256:a9d8bee7b134856cc8baa142494a177f2ba9ecfededfcdd38f634e14cca8aae2").unwrap();
+        assert!(cache.entries().unwrap().is_empty());
+
+        cache.insert(&key(3), &meta, &checksum()).unwrap();
+        cache.ttl = Some(Duration::from_secs(5));
+        let path = cache.entry_path(&key(3)).unwrap();
+        let mut entry = cache.get(&key(3)).unwrap().expect("not expired yet");
+        entry.created_at -= 10;
+        std::fs::write(&path, serde_json::to_vec(&entry).unwrap()).unwrap();
+        assert!(cache.get(&key(3)).unwrap().is_none());
+        assert!(cache.entries().unwrap().is_empty());
+        assert!(path.exists(), "reads don't remove expired entries");
+        cache.prune().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_invalid_keys() {
+        let (_tempdir, cache) = open();
+        let meta: ContractSourceMetadata = serde_json::from_str(META).unwrap();
+        let checksum = SHA256Checksum {
+            hash: vec![0xab; 32],
+        };
+        for invalid in ["../../x", "", &key(0xab).to_uppercase(), &key(0xab)[1..]] {
+            assert!(cache.get(invalid).is_err());
+            assert!(cache.insert(invalid, &meta, &checksum).is_err());
+            assert!(cache.invalidate(invalid).is_err());
+        }
+        // files, which aren't entries, are skipped
+        std::fs::write(cache.dir().join("notes.json"), b"{}").unwrap();
+        cache.insert(&key(1), &meta, &checksum).unwrap();
+        assert_eq!(cache.entries().unwrap().len(), 1);
+        cache.clear().unwrap();
+        assert!(cache.dir().join("notes.json").exists());
+    }
+
+    #[test]
+    fn test_concurrent_inserts() {
+        let (_tempdir, cache) = open();
+        let meta: ContractSourceMetadata = serde_json::from_str(META).unwrap();
+        std::thread::scope(|scope| {
+            for byte in 0..8u8 {
+                let (cache, meta) = (&cache, &meta);
+                scope.spawn(move || {
+                    let checksum = SHA256Checksum {
+                        hash: vec![byte; 32],
+                    };
+                    cache.insert(&key(0), meta, &checksum).unwrap();
+                });
+            }
+        });
+        assert!(cache.get(&key(0)).unwrap().is_some());
+        assert_eq!(std::fs::read_dir(cache.dir()).unwrap().count(), 1);
+    }
+}
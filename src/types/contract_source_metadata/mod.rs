@@ -4,6 +4,7 @@ use serde::{Deserialize, Serialize};
 use crate::env_keys;
 
 mod validate;
+pub use validate::docker_image_digest;
 /// The struct provides information about deployed contract's source code and supported standards.
 ///
 /// Contract source metadata follows [**NEP-330 standard**](https://github.com/near/NEPs/blob/master/neps/nep-0330.md) for smart contracts
@@ -82,35 +83,35 @@ pub struct ContractSourceMetadata {
 
 impl ContractSourceMetadata {
     pub fn docker_env_args(&self) -> Vec<String> {
+        self.docker_env_vars()
+            .into_iter()
+            .flat_map(|env_var| ["--env".to_string(), env_var])
+            .collect()
+    }
+
+    /// `KEY=VALUE` environment variables of build container, see [env_keys]
+    pub fn docker_env_vars(&self) -> Vec<String> {
         let mut result = vec![];
         if let Some(ref build_info) = self.build_info {
-            result.extend(vec![
-                "--env".to_string(),
-                format!(
-                    "{}={}",
-                    env_keys::BUILD_ENVIRONMENT,
-                    build_info.build_environment
-                ),
-            ]);
-            result.extend(vec![
-                "--env".to_string(),
-                format!(
-                    "{}={}",
-                    env_keys::SOURCE_CODE_SNAPSHOT,
-                    build_info.source_code_snapshot
-                ),
-            ]);
-            result.extend(vec![
-                "--env".to_string(),
-                format!("{}={}", env_keys::CONTRACT_PATH, build_info.contract_path),
-            ]);
+            result.push(format!(
+                "{}={}",
+                env_keys::BUILD_ENVIRONMENT,
+                build_info.build_environment
+            ));
+            result.push(format!(
+                "{}={}",
+                env_keys::SOURCE_CODE_SNAPSHOT,
+                build_info.source_code_snapshot
+            ));
+            result.push(format!(
+                "{}={}",
+                env_keys::CONTRACT_PATH,
+                build_info.contract_path
+            ));
         }
 
         if let Some(ref repo_link_hint) = self.link {
-            result.extend(vec![
-                "--env".to_string(),
-                format!("{}={}", env_keys::LINK, repo_link_hint),
-            ]);
+            result.push(format!("{}={}", env_keys::LINK, repo_link_hint));
         }
 
         result
@@ -6,8 +6,30 @@ use super::BuildInfo;
 pub const DOCKER_IMAGE_REGEX_PATTERN: &str =
     r#"^(?P<image>[^:@\s]+?)(?::(?P<tag>[^@\s]+?))?(@sha256:(?P<digest>[a-f0-9]{64}))$"#;
 
+/// `sha256:<hex>` digest, which `build_environment` docker image reference is pinned to
+pub fn docker_image_digest(build_environment: &str) -> eyre::Result<String> {
+    let regex = regex::Regex::new(DOCKER_IMAGE_REGEX_PATTERN).expect("no error");
+    regex
+        .captures(build_environment)
+        .and_then(|captures| captures.name("digest"))
+        .map(|capture| format!("sha256:{}", capture.as_str()))
+        .ok_or(eyre::eyre!(
+            "`{}` didn't match any `digest` group in {}",
+            build_environment,
+            DOCKER_IMAGE_REGEX_PATTERN
+        ))
+}
+
 impl super::ContractSourceMetadata {
     pub fn validate(&self, whitelist: Option<Whitelist>) -> eyre::Result<()> {
+        let _span = tracing::info_span!(
+            "validate",
+            snapshot = self
+                .build_info
+                .as_ref()
+                .map(|build_info| build_info.source_code_snapshot.as_str()),
+        )
+        .entered();
         if self.build_info.is_none() {
             return Err(eyre::eyre!(
                 "`build_info` field of `ContractSourceMetadata` cannot be null"
@@ -114,6 +136,12 @@ impl super::build_info::BuildInfo {
 
     pub fn validate_build_command_on_whitelist(&self, entry: WhitelistEntry) -> eyre::Result<()> {
         let expected_cmd_len = entry.expected_command_prefix.len();
+        if expected_cmd_len == 0 {
+            return Err(eyre::eyre!(
+                "empty expected whitelist command prefix for `{}`, check whitelist with `near_verify_rs::types::whitelist::lint`",
+                entry.expected_docker_image
+            ));
+        }
         if (self.build_command.len() < expected_cmd_len)
             || (self.build_command[1..expected_cmd_len]
                 != entry.expected_command_prefix[1..expected_cmd_len])
@@ -150,4 +178,15 @@ mod tests {
         let wrong_haystack_b = "sourcescan/cargo-near:0.13.4-rust-1.85.0@sha256:a9d8bee7b134856cc8baa142494a177f2ba9ecfededfcdd38f634e14cca8aae2 ";
         assert!(!regex.is_match(wrong_haystack_b));
     }
+
+    #[test]
+    fn check_docker_image_digest() {
+        let digest = super::docker_image_digest("sourcescan/cargo-near:0.13.4-rust-1.85.0@sha256:a9d8bee7b134856cc8baa142494a177f2ba9ecfededfcdd38f634e14cca8aae2").expect("no error");
+        assert_eq!(
+            "sha256:a9d8bee7b134856cc8baa142494a177f2ba9ecfededfcdd38f634e14cca8aae2",
+            digest
+        );
+
+        assert!(super::docker_image_digest("sourcescan/cargo-near:0.13.4-rust-1.85.0").is_err());
+    }
 }
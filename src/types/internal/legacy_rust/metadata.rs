@@ -1,56 +1,79 @@
 use camino::{Utf8Path, Utf8PathBuf};
-use cargo_metadata::{MetadataCommand, Package};
 use eyre::{ContextCompat, WrapErr};
 
-use crate::pretty_print;
-
 use super::manifest_path::ManifestPath;
+use super::target_dir::{self, BuildCommandOverrides};
+use super::workspace::{self, Manifest};
 pub const EXPECTED_EXTENSION: &str = "wasm";
 
+const TARGET_DIR: &str = "target";
+/// Subdirectory of `target`, where `cargo near` puts its artifacts
+const CARGO_NEAR_DIR: &str = "near";
+
 /// Relevant metadata obtained from Cargo.toml.
 #[derive(Debug)]
 pub struct CrateMetadata {
-    pub root_package: Package,
+    pub package_name: String,
+    /// Directory of `cargo near` artifacts, `<target dir>/near[/<package>]` or `--out-dir`
     pub target_directory: Utf8PathBuf,
 }
-/// Create the directory if it doesn't exist, and return the absolute path to it.
-fn force_canonicalize_dir(dir: &Utf8Path) -> eyre::Result<Utf8PathBuf> {
-    std::fs::create_dir_all(dir)
-        .wrap_err_with(|| format!("failed to create directory `{}`", dir))?;
-    // use canonicalize from `dunce` create instead of default one from std because it's compatible with Windows UNC paths
-    // and don't break cargo compilation on Windows
-    // https://github.com/rust-lang/rust/issues/42869
-    Utf8PathBuf::from_path_buf(
-        dunce::canonicalize(dir)
-            .wrap_err_with(|| format!("failed to canonicalize path: {} ", dir))?,
-    )
-    .map_err(|err| eyre::eyre!("failed to convert path {}", err.to_string_lossy()))
-}
 
 impl CrateMetadata {
-    /// Parses the contract manifest and returns relevant metadata.
-    pub fn collect(manifest_path: ManifestPath, no_locked: bool) -> eyre::Result<Self> {
-        let (metadata, root_package) = {
-            let (mut metadata, root_package) = get_cargo_metadata(&manifest_path, no_locked)?;
-            metadata.target_directory = force_canonicalize_dir(&metadata.target_directory)?;
-            metadata.workspace_root = metadata.workspace_root.canonicalize_utf8()?;
-            (metadata, root_package)
-        };
-
-        let mut target_directory = force_canonicalize_dir(&metadata.target_directory.join("near"))?;
+    /// Parses the contract manifest and manifest of its workspace root, if any, and returns relevant metadata.
+    ///
+    /// Manifests are parsed statically, without running `cargo metadata` on host,
+    /// and workspace root is only looked for up to `source_root`.
+    ///
+    /// Target directory is overridden, in order of precedence, by `--out-dir` and
+    /// `--env CARGO_TARGET_DIR=<dir>` of `build_command`, and by `build.target-dir` of `.cargo/config.toml`
+    pub fn collect(
+        manifest_path: ManifestPath,
+        source_root: &Utf8Path,
+        build_command: &[String],
+    ) -> eyre::Result<Self> {
+        tracing::info!(
+            target: "near_teach_me",
+            "Parsing manifest {}", manifest_path.path
+        );
+        let manifest = Manifest::read(&manifest_path.path)?;
+        let package = manifest.package.as_ref().wrap_err_with(|| {
+            format!(
+                "`{}` has no `[package]` section.\n\
+                It's likely a manifest of virtual workspace and not of a contract's crate",
+                manifest_path.path
+            )
+        })?;
+        let source_root = source_root
+            .canonicalize_utf8()
+            .wrap_err_with(|| format!("failed to canonicalize path: {}", source_root))?;
+        let absolute_manifest_dir = manifest_path.directory()?;
+        let workspace_root = workspace::root(absolute_manifest_dir, &manifest, &source_root)?;
 
         // Normalize the package and lib name.
-        let package_name = root_package.name.replace('-', "_");
+        let package_name = package.name.replace('-', "_");
 
-        let absolute_manifest_dir = manifest_path.directory()?;
-        if absolute_manifest_dir != metadata.workspace_root {
-            // If the contract is a package in a workspace, we use the package name
-            // as the name of the sub-folder where we put the `.contract` bundle.
-            target_directory = force_canonicalize_dir(&target_directory.join(package_name))?;
-        }
+        let overrides = BuildCommandOverrides::parse(build_command);
+        let target_directory = if let Some(ref out_dir) = overrides.out_dir {
+            target_dir::host_path(out_dir, absolute_manifest_dir, &source_root)?
+        } else {
+            let target_dir = match overrides.target_dir {
+                Some(ref target_dir) => {
+                    target_dir::host_path(target_dir, absolute_manifest_dir, &source_root)?
+                }
+                None => target_dir::from_cargo_config(absolute_manifest_dir, &source_root)?
+                    .unwrap_or_else(|| workspace_root.join(TARGET_DIR)),
+            };
+            let mut target_directory = target_dir.join(CARGO_NEAR_DIR);
+            if absolute_manifest_dir != workspace_root {
+                // If the contract is a package in a workspace, we use the package name
+                // as the name of the sub-folder where we put the `.contract` bundle.
+                target_directory.push(&package_name);
+            }
+            target_directory
+        };
 
         let crate_metadata = CrateMetadata {
-            root_package,
+            package_name,
             target_directory,
         };
         tracing::trace!("crate metadata : {:#?}", crate_metadata);
@@ -61,62 +84,140 @@ impl CrateMetadata {
         let result = self.target_directory.clone();
         tracing::info!(
             target: "near_teach_me",
-            parent: &tracing::Span::none(),
             "Resolved output directory: {}", result
         );
         Ok(result)
     }
-    fn formatted_package_name(&self) -> String {
-        self.root_package.name.replace('-', "_")
-    }
 
     pub fn get_legacy_cargo_near_output_path(&self) -> eyre::Result<camino::Utf8PathBuf> {
         let output_dir = self.resolve_output_dir()?;
 
-        let filename = format!("{}.{}", self.formatted_package_name(), EXPECTED_EXTENSION);
+        let filename = format!("{}.{}", self.package_name, EXPECTED_EXTENSION);
 
         Ok(output_dir.join(filename))
     }
 }
-/// Get the result of `cargo metadata`, together with the root package id.
-fn get_cargo_metadata(
-    manifest_path: &ManifestPath,
-    no_locked: bool,
-) -> eyre::Result<(cargo_metadata::Metadata, Package)> {
-    tracing::info!(
-        target: "near_teach_me",
-        parent: &tracing::Span::none(),
-        "Fetching cargo metadata for {}", manifest_path.path
-    );
-    let mut cmd = MetadataCommand::new();
-    if !no_locked {
-        cmd.other_options(["--locked".to_string()]);
+
+#[cfg(test)]
+mod tests {
+    use camino::Utf8PathBuf;
+
+    use super::CrateMetadata;
+    use crate::types::internal::legacy_rust::ManifestPath;
+
+    fn fixture(name: &str) -> Utf8PathBuf {
+        Utf8PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/resources/legacy_target_dir")
+            .join(name)
+            .canonicalize_utf8()
+            .unwrap()
+    }
+
+    fn output_path(
+        source_root: &Utf8PathBuf,
+        contract_path: &str,
+        build_command: &[&str],
+    ) -> eyre::Result<Utf8PathBuf> {
+        let manifest_path =
+            ManifestPath::try_from(source_root.join(contract_path).join("Cargo.toml"))?;
+        let build_command = build_command
+            .iter()
+            .map(|token| token.to_string())
+            .collect::<Vec<_>>();
+        CrateMetadata::collect(manifest_path, source_root, &build_command)?
+            .get_legacy_cargo_near_output_path()
+    }
+
+    #[test]
+    fn test_default_target_dir() {
+        let root = fixture("standalone");
+        assert_eq!(
+            output_path(&root, "", &["cargo", "near", "build"]).unwrap(),
+            root.join("target/near/standalone_contract.wasm")
+        );
+    }
+
+    #[test]
+    fn test_cargo_config_target_dir() {
+        let root = fixture("config");
+        assert_eq!(
+            output_path(&root, "", &["cargo", "near", "build"]).unwrap(),
+            root.join("custom-target/near/config_contract.wasm")
+        );
+
+        let root = fixture("workspace");
+        assert_eq!(
+            output_path(&root, "contracts/ft", &["cargo", "near", "build"]).unwrap(),
+            root.join("build/near/ft_contract/ft_contract.wasm")
+        );
+        // `.cargo/config` takes precedence over `.cargo/config.toml` in the same directory
+        let root = fixture("both_configs");
+        assert_eq!(
+            output_path(&root, "", &["cargo", "near", "build"]).unwrap(),
+            root.join("legacy-config-target/near/both_configs_contract.wasm")
+        );
+
+        // only one config file per directory is read, config of parent directory applies
+        let root = fixture("config");
+        assert_eq!(
+            output_path(&root, "nested", &["cargo", "near", "build"]).unwrap(),
+            root.join("custom-target/near/nested_contract.wasm")
+        );
     }
-    let cmd = cmd.manifest_path(&manifest_path.path);
-    tracing::info!(
-        target: "near_teach_me",
-        parent: &tracing::Span::none(),
-        "Command execution:\n{}",
-        pretty_print::indent_payload(&format!("{:#?}", cmd.cargo_command()))
-    );
-    let metadata = cmd.exec();
-    if let Err(cargo_metadata::Error::CargoMetadata { stderr }) = metadata.as_ref() {
-        if stderr.contains("remove the --locked flag") {
-            return Err(cargo_metadata::Error::CargoMetadata {
-                stderr: stderr.clone(),
-            })
-            .wrap_err("Cargo.lock is absent or not up-to-date");
-        }
+
+    #[test]
+    fn test_build_command_target_dir() {
+        let root = fixture("workspace");
+        assert_eq!(
+            output_path(
+                &root,
+                "contracts/ft",
+                &[
+                    "cargo",
+                    "near",
+                    "build",
+                    "--env",
+                    "CARGO_TARGET_DIR=../../env-target"
+                ],
+            )
+            .unwrap(),
+            root.join("env-target/near/ft_contract/ft_contract.wasm")
+        );
+        assert_eq!(
+            output_path(
+                &root,
+                "contracts/ft",
+                &[
+                    "cargo",
+                    "near",
+                    "build",
+                    "--env=CARGO_TARGET_DIR=/home/near/code/env-target",
+                    "--out-dir",
+                    "out",
+                ],
+            )
+            .unwrap(),
+            root.join("contracts/ft/out/ft_contract.wasm")
+        );
+
+        let err = output_path(
+            &root,
+            "contracts/ft",
+            &[
+                "cargo",
+                "near",
+                "build",
+                "--env",
+                "CARGO_TARGET_DIR=/tmp/target",
+            ],
+        )
+        .expect_err("target dir isn't mounted from host");
+        assert!(err.to_string().contains("outside of source code"));
+        assert!(output_path(
+            &root,
+            "contracts/ft",
+            &["cargo", "near", "build", "--out-dir", "../../.."]
+        )
+        .is_err());
     }
-    let metadata = metadata
-        .wrap_err("Error invoking `cargo metadata`. Your `Cargo.toml` file is likely malformed")?;
-    let root_package = metadata
-        .root_package()
-        .wrap_err(
-            "raw_metadata.root_package() returned None.\n\
-            Command was likely called from a root of virtual workspace as current directory \
-            and not from a contract's crate",
-        )?
-        .clone();
-    Ok((metadata, root_package))
 }
@@ -0,0 +1,165 @@
+use camino::{Utf8Path, Utf8PathBuf};
+use eyre::WrapErr;
+use serde::Deserialize;
+
+use crate::logic::NEP330_REPO_MOUNT;
+
+use super::workspace::normalize;
+
+const CARGO_TARGET_DIR_ENV: &str = "CARGO_TARGET_DIR";
+/// Config files in `.cargo` directory, only the first existing one is read, as `cargo` does it
+const CONFIG_FILE_NAMES: [&str; 2] = ["config", "config.toml"];
+
+/// Overrides of `cargo near` output location in `build_command`,
+/// paths are as seen in build container
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct BuildCommandOverrides {
+    /// `--env CARGO_TARGET_DIR=<dir>`
+    pub target_dir: Option<String>,
+    /// `--out-dir <dir>`, final artifacts are copied there directly
+    pub out_dir: Option<String>,
+}
+
+impl BuildCommandOverrides {
+    pub fn parse(build_command: &[String]) -> Self {
+        let mut result = Self::default();
+        let mut tokens = build_command.iter();
+        while let Some(token) = tokens.next() {
+            let mut flag_value = |flag: &str| match token.strip_prefix(flag) {
+                Some("") => tokens.next().cloned(),
+                Some(value) => value.strip_prefix('=').map(str::to_string),
+                None => None,
+            };
+            if let Some(env) = flag_value("--env") {
+                if let Some(dir) = env
+                    .strip_prefix(CARGO_TARGET_DIR_ENV)
+                    .and_then(|rest| rest.strip_prefix('='))
+                {
+                    result.target_dir = Some(dir.to_string());
+                }
+            } else if let Some(dir) = flag_value("--out-dir") {
+                result.out_dir = Some(dir);
+            }
+        }
+        result
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CargoConfig {
+    #[serde(default)]
+    build: BuildConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct BuildConfig {
+    target_dir: Option<String>,
+}
+
+/// `build.target-dir` of `.cargo/config` or `.cargo/config.toml` nearest to `crate_dir`, looked for up to `source_root`,
+/// as `cargo` runs in `crate_dir` in build container
+///
+/// Relative `target-dir` is resolved against parent of `.cargo` directory
+pub fn from_cargo_config(
+    crate_dir: &Utf8Path,
+    source_root: &Utf8Path,
+) -> eyre::Result<Option<Utf8PathBuf>> {
+    for dir in crate_dir
+        .ancestors()
+        .take_while(|dir| dir.starts_with(source_root))
+    {
+        let Some(path) = CONFIG_FILE_NAMES
+            .iter()
+            .map(|file_name| dir.join(".cargo").join(file_name))
+            .find(|path| path.is_file())
+        else {
+            continue;
+        };
+        let content =
+            std::fs::read_to_string(&path).wrap_err_with(|| format!("couldn't read `{}`", path))?;
+        let config: CargoConfig =
+            toml::from_str(&content).wrap_err_with(|| format!("`{}` is malformed", path))?;
+        if let Some(target_dir) = config.build.target_dir {
+            tracing::info!(
+                target: "near_teach_me",
+                "`build.target-dir` of `{}`: {}", path, target_dir
+            );
+            return host_path(&target_dir, dir, source_root).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+/// Maps `path` in build container to host, relative `path` is resolved against host `base` directory
+///
+/// Absolute `path` has to be in mounted source code, as nothing else is accessible from host
+pub fn host_path(path: &str, base: &Utf8Path, source_root: &Utf8Path) -> eyre::Result<Utf8PathBuf> {
+    let path = Utf8Path::new(path);
+    let host_path = if path.is_absolute() {
+        let relative = path.strip_prefix(NEP330_REPO_MOUNT).map_err(|_| {
+            eyre::eyre!(
+                "`{}` is outside of source code, mounted at `{}` in build container, \
+                so artifact can't be located on host",
+                path,
+                NEP330_REPO_MOUNT
+            )
+        })?;
+        normalize(&source_root.join(relative))
+    } else {
+        normalize(&base.join(path))
+    };
+    if !host_path.starts_with(source_root) {
+        return Err(eyre::eyre!(
+            "`{}` resolves to `{}`, outside of source code `{}`",
+            path,
+            host_path,
+            source_root
+        ));
+    }
+    Ok(host_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BuildCommandOverrides;
+
+    #[test]
+    fn test_parse_build_command() {
+        let parse = |build_command: &[&str]| {
+            BuildCommandOverrides::parse(
+                &build_command
+                    .iter()
+                    .map(|token| token.to_string())
+                    .collect::<Vec<_>>(),
+            )
+        };
+        assert_eq!(
+            parse(&["cargo", "near", "build", "--locked"]),
+            BuildCommandOverrides::default()
+        );
+        assert_eq!(
+            parse(&[
+                "cargo",
+                "near",
+                "build",
+                "--env",
+                "KEY=VALUE",
+                "--env=CARGO_TARGET_DIR=/home/near/code/target-env",
+                "--out-dir",
+                "out",
+            ]),
+            BuildCommandOverrides {
+                target_dir: Some("/home/near/code/target-env".into()),
+                out_dir: Some("out".into()),
+            }
+        );
+        assert_eq!(
+            parse(&["cargo", "near", "build", "--out-dir=out", "--environment"]),
+            BuildCommandOverrides {
+                target_dir: None,
+                out_dir: Some("out".into()),
+            }
+        );
+    }
+}
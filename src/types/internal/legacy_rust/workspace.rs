@@ -0,0 +1,305 @@
+use std::collections::BTreeMap;
+
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+use eyre::WrapErr;
+use serde::Deserialize;
+
+use super::manifest_path::MANIFEST_FILE_NAME;
+
+/// Subset of `Cargo.toml`, needed to locate legacy `cargo near` artifacts
+#[derive(Debug, Deserialize)]
+pub struct Manifest {
+    pub package: Option<Package>,
+    pub workspace: Option<Workspace>,
+    #[serde(flatten)]
+    dependencies: Dependencies,
+    #[serde(default)]
+    target: BTreeMap<String, Dependencies>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Package {
+    pub name: String,
+    /// Explicit path to workspace root, `package.workspace`
+    pub workspace: Option<Utf8PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Workspace {
+    #[serde(default)]
+    pub members: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Dependencies {
+    #[serde(default)]
+    dependencies: toml::Table,
+    #[serde(default)]
+    dev_dependencies: toml::Table,
+    #[serde(default)]
+    build_dependencies: toml::Table,
+}
+
+impl Manifest {
+    pub fn read(path: &Utf8Path) -> eyre::Result<Self> {
+        let content =
+            std::fs::read_to_string(path).wrap_err_with(|| format!("couldn't read `{}`", path))?;
+        toml::from_str(&content).wrap_err_with(|| format!("`{}` is malformed", path))
+    }
+
+    /// `path` dependencies of all kinds and targets, as written in manifest
+    fn path_dependencies(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(&self.dependencies)
+            .chain(self.target.values())
+            .flat_map(|dependencies| {
+                dependencies
+                    .dependencies
+                    .values()
+                    .chain(dependencies.dev_dependencies.values())
+                    .chain(dependencies.build_dependencies.values())
+            })
+            .filter_map(|dependency| dependency.get("path")?.as_str())
+    }
+}
+
+enum Membership {
+    Member,
+    Excluded,
+    NotMember,
+}
+
+/// Root directory of workspace, which crate with `manifest` in `manifest_dir` belongs to,
+/// or `manifest_dir` itself for a standalone crate
+///
+/// Workspace root is looked for the same way `cargo` does it, but only up to `source_root`,
+/// as nothing above it is mounted into build container.
+/// All paths are expected to be canonical.
+pub fn root(
+    manifest_dir: &Utf8Path,
+    manifest: &Manifest,
+    source_root: &Utf8Path,
+) -> eyre::Result<Utf8PathBuf> {
+    if manifest.workspace.is_some() {
+        return Ok(manifest_dir.to_path_buf());
+    }
+    if let Some(explicit) = manifest
+        .package
+        .as_ref()
+        .and_then(|package| package.workspace.as_ref())
+    {
+        let root = normalize(&manifest_dir.join(explicit));
+        if !root.starts_with(source_root) {
+            return Err(eyre::eyre!(
+                "`package.workspace` of `{}` points to `{}`, outside of source code `{}`",
+                manifest_dir,
+                root,
+                source_root
+            ));
+        }
+        let root_manifest = Manifest::read(&root.join(MANIFEST_FILE_NAME))?;
+        if root_manifest.workspace.is_none() {
+            return Err(eyre::eyre!(
+                "`package.workspace` of `{}` points to `{}`, which isn't a workspace root",
+                manifest_dir,
+                root
+            ));
+        }
+        return Ok(root);
+    }
+    for dir in manifest_dir
+        .ancestors()
+        .skip(1)
+        .take_while(|dir| dir.starts_with(source_root))
+    {
+        let path = dir.join(MANIFEST_FILE_NAME);
+        if !path.is_file() {
+            continue;
+        }
+        let root_manifest = Manifest::read(&path)?;
+        let Some(ref workspace) = root_manifest.workspace else {
+            continue;
+        };
+        return match membership(dir, workspace, &root_manifest, manifest_dir) {
+            Membership::Member => Ok(dir.to_path_buf()),
+            Membership::Excluded => Ok(manifest_dir.to_path_buf()),
+            Membership::NotMember => Err(eyre::eyre!(
+                "crate `{}` believes it's in workspace `{}` when it's not, \
+                it has to be added to `workspace.members` or `workspace.exclude`",
+                manifest_dir,
+                dir
+            )),
+        };
+    }
+    Ok(manifest_dir.to_path_buf())
+}
+
+fn membership(
+    root: &Utf8Path,
+    workspace: &Workspace,
+    root_manifest: &Manifest,
+    crate_dir: &Utf8Path,
+) -> Membership {
+    let Ok(relative) = crate_dir.strip_prefix(root) else {
+        return Membership::NotMember;
+    };
+    let relative = components(relative);
+    if workspace
+        .members
+        .iter()
+        .any(|pattern| glob_match(&components(&normalize(Utf8Path::new(pattern))), &relative))
+    {
+        return Membership::Member;
+    }
+    if workspace
+        .exclude
+        .iter()
+        .any(|excluded| crate_dir.starts_with(normalize(&root.join(excluded))))
+    {
+        return Membership::Excluded;
+    }
+    if root_manifest
+        .path_dependencies()
+        .any(|path| normalize(&root.join(path)) == crate_dir)
+    {
+        return Membership::Member;
+    }
+    Membership::NotMember
+}
+
+/// Resolves `.` and `..` components of `path` lexically, without touching file system
+pub(super) fn normalize(path: &Utf8Path) -> Utf8PathBuf {
+    let mut result = Utf8PathBuf::new();
+    for component in path.components() {
+        match component {
+            Utf8Component::CurDir => {}
+            Utf8Component::ParentDir => {
+                result.pop();
+            }
+            component => result.push(component),
+        }
+    }
+    result
+}
+
+fn components(path: &Utf8Path) -> Vec<&str> {
+    path.components()
+        .map(|component| component.as_str())
+        .collect()
+}
+
+/// Matches `workspace.members` glob, where `*` and `?` match within a path component,
+/// and `**` matches any number of components
+fn glob_match(pattern: &[&str], path: &[&str]) -> bool {
+    match (pattern.split_first(), path.split_first()) {
+        (None, None) => true,
+        (Some((&"**", rest)), _) => {
+            glob_match(rest, path) || (!path.is_empty() && glob_match(pattern, &path[1..]))
+        }
+        (Some((component_pattern, pattern_rest)), Some((component, path_rest))) => {
+            component_match(component_pattern.as_bytes(), component.as_bytes())
+                && glob_match(pattern_rest, path_rest)
+        }
+        _ => false,
+    }
+}
+
+fn component_match(pattern: &[u8], text: &[u8]) -> bool {
+    match (pattern.split_first(), text.split_first()) {
+        (None, None) => true,
+        (Some((b'*', rest)), _) => {
+            component_match(rest, text)
+                || (!text.is_empty() && component_match(pattern, &text[1..]))
+        }
+        (Some((b'?', rest)), Some((_, text_rest))) => component_match(rest, text_rest),
+        (Some((expected, rest)), Some((actual, text_rest))) => {
+            expected == actual && component_match(rest, text_rest)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use camino::{Utf8Path, Utf8PathBuf};
+
+    use super::{root, Manifest};
+
+    fn write(dir: &Utf8Path, path: &str, content: &str) {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    fn resolve(source_root: &Utf8Path, crate_dir: &str) -> eyre::Result<Utf8PathBuf> {
+        let manifest_dir = source_root.join(crate_dir);
+        let manifest = Manifest::read(&manifest_dir.join("Cargo.toml"))?;
+        root(&manifest_dir, &manifest, source_root)
+    }
+
+    #[test]
+    fn test_workspace_root() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let source_root =
+            Utf8PathBuf::from_path_buf(tempdir.path().canonicalize().unwrap()).unwrap();
+        let package = |name: &str| format!("[package]\nname = \"{}\"\n", name);
+        write(
+            &source_root,
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"contracts/*\"]\nexclude = [\"standalone\"]\n\n\
+            [package]\nname = \"root\"\n\n[dependencies]\nhelper = { path = \"./helper\" }\n",
+        );
+        write(&source_root, "contracts/ft/Cargo.toml", &package("ft"));
+        write(&source_root, "helper/Cargo.toml", &package("helper"));
+        write(
+            &source_root,
+            "standalone/Cargo.toml",
+            &package("standalone"),
+        );
+        write(&source_root, "stray/Cargo.toml", &package("stray"));
+        write(
+            &source_root,
+            "nested/Cargo.toml",
+            "[package]\nname = \"nested\"\nworkspace = \"..\"\n",
+        );
+
+        assert_eq!(resolve(&source_root, "").unwrap(), source_root);
+        assert_eq!(resolve(&source_root, "contracts/ft").unwrap(), source_root);
+        assert_eq!(resolve(&source_root, "helper").unwrap(), source_root);
+        assert_eq!(resolve(&source_root, "nested").unwrap(), source_root);
+        assert_eq!(
+            resolve(&source_root, "standalone").unwrap(),
+            source_root.join("standalone")
+        );
+        assert!(resolve(&source_root, "stray").is_err());
+
+        write(
+            &source_root,
+            "escaping/Cargo.toml",
+            "[package]\nname = \"escaping\"\nworkspace = \"../..\"\n",
+        );
+        let err = resolve(&source_root, "escaping").expect_err("workspace outside of source");
+        assert!(err.to_string().contains("outside of source code"));
+
+        // workspace above `source_root` isn't visible in build container
+        let subdir = source_root.join("contracts/ft");
+        assert_eq!(resolve(&subdir, "").unwrap(), subdir);
+    }
+
+    #[test]
+    fn test_glob_match() {
+        let glob_match = |pattern: &str, path: &str| {
+            super::glob_match(
+                &pattern.split('/').collect::<Vec<_>>(),
+                &path.split('/').collect::<Vec<_>>(),
+            )
+        };
+        assert!(glob_match("contracts/*", "contracts/ft"));
+        assert!(glob_match("contracts/ft-?", "contracts/ft-1"));
+        assert!(glob_match("**/ft", "a/b/ft"));
+        assert!(!glob_match("contracts/*", "contracts/ft/nested"));
+        assert!(!glob_match("contracts/f*t", "contracts/nft"));
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::BTreeMap;
+
+use camino::Utf8Path;
+use serde::{Deserialize, Serialize};
+
+use crate::types::whitelist::{self, LintReport, Whitelist};
+
+mod signature;
+
+pub use signature::{
+    verify_signature, BadSignatureError, TrustedKey, UnsignedError, ED25519_SIGNATURE_EXTENSION,
+    MINISIGN_SIGNATURE_EXTENSION,
+};
+
+/// Latest version of [Policy] document, which is supported
+pub const POLICY_VERSION: u32 = 1;
+
+/// Versioned verification policy document, loadable from JSON or TOML
+///
+/// ## Examples:
+///
+/// ```toml
+/// version = 1
+/// description = "images, approved for mainnet verification"
+///
+/// [metadata]
+/// maintainer = "verification-team"
+///
+/// [[whitelist]]
+/// expected_docker_image = "sourcescan/cargo-near"
+/// expected_command_prefix = ["cargo", "near", "build"]
+/// ```
+///
+/// `version` is checked first, so that documents of future versions are declined as unsupported.
+/// Unknown fields of supported versions are rejected, e.g. a misspelled `whitelist`
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    pub version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Free-form string metadata, e.g. maintainer or date of approval
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    #[serde(default)]
+    pub whitelist: Whitelist,
+}
+
+impl Policy {
+    /// Policy of a legacy bare-array whitelist
+    pub fn from_whitelist(whitelist: Whitelist) -> Self {
+        Self {
+            version: POLICY_VERSION,
+            whitelist,
+            ..Default::default()
+        }
+    }
+
+    /// Parses a policy document, or a bare array of whitelist entries of legacy whitelist files
+    pub fn from_json(content: &[u8]) -> eyre::Result<Self> {
+        let value: serde_json::Value = serde_json::from_slice(content)
+            .map_err(|err| eyre::eyre!("malformed policy json: {}", err))?;
+        if value.is_array() {
+            let whitelist: Whitelist = serde_json::from_value(value)
+                .map_err(|err| eyre::eyre!("malformed whitelist json: {}", err))?;
+            return Ok(Self::from_whitelist(whitelist));
+        }
+        let version: Version = serde_json::from_value(value.clone())
+            .map_err(|err| eyre::eyre!("malformed policy json: {}", err))?;
+        check_version(version.version)?;
+        serde_json::from_value(value).map_err(|err| eyre::eyre!("malformed policy json: {}", err))
+    }
+
+    pub fn from_toml(content: &str) -> eyre::Result<Self> {
+        let version: Version =
+            toml::from_str(content).map_err(|err| eyre::eyre!("malformed policy toml: {}", err))?;
+        check_version(version.version)?;
+        toml::from_str(content).map_err(|err| eyre::eyre!("malformed policy toml: {}", err))
+    }
+
+    /// Loads a policy document, format is chosen by extension: `.toml` or json otherwise
+    pub fn load(path: &Utf8Path) -> eyre::Result<Self> {
+        let content =
+            std::fs::read(path).map_err(|err| eyre::eyre!("couldn't read `{}`: {}", path, err))?;
+        Self::parse(path, &content)
+    }
+
+    /// Same as [Policy::load], but the file has to have a detached signature next to it,
+    /// `<path>.minisig` or `<path>.sig`, made with one of `trusted_keys`
+    ///
+    /// Fails with [UnsignedError] or [BadSignatureError] otherwise
+    pub fn load_signed(path: &Utf8Path, trusted_keys: &[TrustedKey]) -> eyre::Result<Self> {
+        let content =
+            std::fs::read(path).map_err(|err| eyre::eyre!("couldn't read `{}`: {}", path, err))?;
+        verify_signature(path, &content, trusted_keys)?;
+        Self::parse(path, &content)
+    }
+
+    /// Same as [Policy::load], but `content` has already been read from `path`
+    pub fn parse(path: &Utf8Path, content: &[u8]) -> eyre::Result<Self> {
+        let result = if path.extension() == Some("toml") {
+            std::str::from_utf8(content)
+                .map_err(|err| eyre::eyre!("{}", err))
+                .and_then(Self::from_toml)
+        } else {
+            Self::from_json(content)
+        };
+        result.map_err(|err| eyre::eyre!("`{}`: {:#}", path, err))
+    }
+
+    /// See [whitelist::lint]
+    pub fn lint(&self) -> LintReport {
+        whitelist::lint(&self.whitelist)
+    }
+}
+
+/// `version` field of a policy document of any version
+#[derive(Deserialize)]
+struct Version {
+    version: u32,
+}
+
+fn check_version(version: u32) -> eyre::Result<()> {
+    if version == 0 || version > POLICY_VERSION {
+        return Err(eyre::eyre!(
+            "unsupported policy version {}, versions up to {} are supported",
+            version,
+            POLICY_VERSION
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Policy;
+
+    #[test]
+    fn test_load_policy_formats() {
+        let legacy = Policy::load("tests/resources/whitelist_ok_nonstandard_image.json".into())
+            .expect("no error");
+        let json = Policy::load("tests/resources/policy_ok_nonstandard_image.json".into())
+            .expect("no error");
+        let toml = Policy::load("tests/resources/policy_ok_nonstandard_image.toml".into())
+            .expect("no error");
+        assert_eq!(legacy.whitelist, json.whitelist);
+        assert_eq!(json, toml);
+        assert_eq!(toml.metadata["maintainer"], "near-verify-rs");
+    }
+
+    #[test]
+    fn test_decline_unsupported_version() {
+        let err = Policy::from_toml("version = 2\nfuture_field = true\n").expect_err("version 2");
+        assert!(format!("{:?}", err).contains("unsupported policy version 2"));
+    }
+
+    #[test]
+    fn test_reject_unknown_fields() {
+        let err = Policy::from_json(br#"{"version": 1, "whitlist": []}"#).expect_err("typo");
+        assert!(format!("{:?}", err).contains("unknown field `whitlist`"));
+
+        let err = Policy::from_toml("version = 1\nfuture_field = true\n").expect_err("unknown");
+        assert!(format!("{:?}", err).contains("unknown field `future_field`"));
+    }
+}
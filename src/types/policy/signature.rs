@@ -0,0 +1,254 @@
+use base64::Engine;
+use camino::{Utf8Path, Utf8PathBuf};
+
+/// Extension of detached raw ed25519 signature file, appended to name of signed file
+pub const ED25519_SIGNATURE_EXTENSION: &str = "sig";
+/// Extension of detached [minisign](https://jedisct1.github.io/minisign/) signature file,
+/// appended to name of signed file
+pub const MINISIGN_SIGNATURE_EXTENSION: &str = "minisig";
+
+const ED25519_KEY_PREFIX: &str = "ed25519:";
+
+/// Public key, which signatures of policy documents are trusted from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrustedKey {
+    /// Raw ed25519 key, its signature is base64 of 64 signature bytes in `<file>.sig`
+    Ed25519(ed25519_dalek::VerifyingKey),
+    /// minisign key, its signature is in `<file>.minisig`, as produced by `minisign -S`
+    Minisign(minisign_verify::PublicKey),
+}
+
+impl TrustedKey {
+    /// Parses `ed25519:<base64 or hex of 32 bytes>`, or minisign public key,
+    /// either as base64 line `RW...` or as whole content of `minisign.pub`
+    pub fn parse(key: &str) -> eyre::Result<Self> {
+        let key = key.trim();
+        if let Some(encoded) = key.strip_prefix(ED25519_KEY_PREFIX) {
+            let bytes = decode_base64_or_hex(encoded)
+                .map_err(|err| eyre::eyre!("malformed ed25519 public key: {}", err))?;
+            let bytes: [u8; ed25519_dalek::PUBLIC_KEY_LENGTH] =
+                bytes.try_into().map_err(|bytes: Vec<u8>| {
+                    eyre::eyre!(
+                        "ed25519 public key has to be {} bytes, got {}",
+                        ed25519_dalek::PUBLIC_KEY_LENGTH,
+                        bytes.len()
+                    )
+                })?;
+            let key = ed25519_dalek::VerifyingKey::from_bytes(&bytes)
+                .map_err(|err| eyre::eyre!("invalid ed25519 public key: {}", err))?;
+            return Ok(Self::Ed25519(key));
+        }
+        let result = if key.lines().count() > 1 {
+            minisign_verify::PublicKey::decode(key)
+        } else {
+            minisign_verify::PublicKey::from_base64(key)
+        };
+        result.map(Self::Minisign).map_err(|err| {
+            eyre::eyre!(
+                "public key has to be either `{}<key>` or minisign public key: {}",
+                ED25519_KEY_PREFIX,
+                err
+            )
+        })
+    }
+
+    fn signature_extension(&self) -> &'static str {
+        match self {
+            Self::Ed25519(_) => ED25519_SIGNATURE_EXTENSION,
+            Self::Minisign(_) => MINISIGN_SIGNATURE_EXTENSION,
+        }
+    }
+
+    fn verify(&self, content: &[u8], signature: &str) -> Result<(), String> {
+        match self {
+            Self::Ed25519(key) => {
+                let bytes = base64::engine::general_purpose::STANDARD
+                    .decode(signature.trim())
+                    .map_err(|err| format!("malformed signature: {}", err))?;
+                let signature = ed25519_dalek::Signature::from_slice(&bytes)
+                    .map_err(|err| format!("malformed signature: {}", err))?;
+                key.verify_strict(content, &signature)
+                    .map_err(|err| err.to_string())
+            }
+            Self::Minisign(key) => {
+                let signature = minisign_verify::Signature::decode(signature)
+                    .map_err(|err| format!("malformed signature: {}", err))?;
+                key.verify(content, &signature, false)
+                    .map_err(|err| err.to_string())
+            }
+        }
+    }
+}
+
+/// Error, returned by [super::Policy::load_signed] when no detached signature of policy file was found
+///
+/// It can be distinguished from other errors with [eyre::Report::downcast_ref]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsignedError {
+    pub path: Utf8PathBuf,
+}
+
+impl std::fmt::Display for UnsignedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "`{}` isn't signed: neither `.{}` nor `.{}` signature file found next to it",
+            self.path, MINISIGN_SIGNATURE_EXTENSION, ED25519_SIGNATURE_EXTENSION
+        )
+    }
+}
+
+impl std::error::Error for UnsignedError {}
+
+/// Error, returned by [super::Policy::load_signed] when detached signatures of policy file
+/// are malformed or weren't made with any of trusted keys
+///
+/// It can be distinguished from other errors with [eyre::Report::downcast_ref]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BadSignatureError {
+    pub path: Utf8PathBuf,
+    /// Reasons of rejection, one per signature file and trusted key
+    pub reasons: Vec<String>,
+}
+
+impl std::fmt::Display for BadSignatureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "`{}` isn't signed with any of trusted keys: {}",
+            self.path,
+            self.reasons.join("; ")
+        )
+    }
+}
+
+impl std::error::Error for BadSignatureError {}
+
+/// Checks that `content` of file at `path` has a detached signature next to it,
+/// made with one of `trusted_keys`, and returns path of that signature file
+pub fn verify_signature(
+    path: &Utf8Path,
+    content: &[u8],
+    trusted_keys: &[TrustedKey],
+) -> eyre::Result<Utf8PathBuf> {
+    if trusted_keys.is_empty() {
+        return Err(eyre::eyre!(
+            "no trusted keys to verify signature of `{}` with",
+            path
+        ));
+    }
+    let mut found = false;
+    let mut reasons = vec![];
+    for extension in [MINISIGN_SIGNATURE_EXTENSION, ED25519_SIGNATURE_EXTENSION] {
+        let signature_path = Utf8PathBuf::from(format!("{}.{}", path, extension));
+        let signature = match std::fs::read_to_string(&signature_path) {
+            Ok(signature) => signature,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => {
+                return Err(eyre::eyre!(
+                    "couldn't read signature `{}`: {}",
+                    signature_path,
+                    err
+                ))
+            }
+        };
+        found = true;
+        for key in trusted_keys
+            .iter()
+            .filter(|key| key.signature_extension() == extension)
+        {
+            match key.verify(content, &signature) {
+                Ok(()) => {
+                    tracing::debug!("`{}` verified with `{}`", path, signature_path);
+                    return Ok(signature_path);
+                }
+                Err(reason) => reasons.push(format!("`{}`: {}", signature_path, reason)),
+            }
+        }
+    }
+    if !found {
+        return Err(eyre::Report::new(UnsignedError {
+            path: path.to_path_buf(),
+        }));
+    }
+    if reasons.is_empty() {
+        reasons.push("no trusted keys of signature's kind".to_string());
+    }
+    Err(eyre::Report::new(BadSignatureError {
+        path: path.to_path_buf(),
+        reasons,
+    }))
+}
+
+fn decode_base64_or_hex(encoded: &str) -> eyre::Result<Vec<u8>> {
+    if encoded.len() == 2 * ed25519_dalek::PUBLIC_KEY_LENGTH {
+        if let Ok(bytes) = hex::decode(encoded) {
+            return Ok(bytes);
+        }
+    }
+    Ok(base64::engine::general_purpose::STANDARD.decode(encoded)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::Engine;
+    use camino::Utf8PathBuf;
+    use ed25519_dalek::Signer;
+
+    use super::{verify_signature, BadSignatureError, TrustedKey, UnsignedError};
+
+    /// Test vector of `minisign-verify`, signature of `test`
+    const MINISIGN_PUBLIC_KEY: &str = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+    const MINISIGN_SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=
+trusted comment: timestamp:1556193335\tfile:test
+y/rUw2y8/hOUYjZU71eHp/Wo1KZ40fGy2VJEDl34XMJM+TX48Ss/17u3IvIfbVR1FkZZSNCisQbuQY+bHwhEBg==
+";
+
+    #[test]
+    fn test_ed25519_signature() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = Utf8PathBuf::from_path_buf(tempdir.path().join("policy.toml")).unwrap();
+        let content = b"version = 1\n";
+        std::fs::write(&path, content).unwrap();
+
+        let signing_key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let trusted = TrustedKey::parse(&format!(
+            "ed25519:{}",
+            hex::encode(signing_key.verifying_key().as_bytes())
+        ))
+        .expect("no error");
+        let other = ed25519_dalek::SigningKey::from_bytes(&[8; 32]);
+        let other = TrustedKey::Ed25519(other.verifying_key());
+
+        let err =
+            verify_signature(&path, content, std::slice::from_ref(&trusted)).expect_err("unsigned");
+        assert!(err.downcast_ref::<UnsignedError>().is_some());
+
+        let signature =
+            base64::engine::general_purpose::STANDARD.encode(signing_key.sign(content).to_bytes());
+        std::fs::write(format!("{}.sig", path), signature).unwrap();
+        assert_eq!(
+            verify_signature(&path, content, &[other.clone(), trusted.clone()]).expect("no error"),
+            format!("{}.sig", path)
+        );
+
+        let err = verify_signature(&path, content, &[other]).expect_err("untrusted key");
+        assert!(err.downcast_ref::<BadSignatureError>().is_some());
+        let err =
+            verify_signature(&path, b"version = 2\n", &[trusted]).expect_err("tampered content");
+        assert!(err.downcast_ref::<BadSignatureError>().is_some());
+    }
+
+    #[test]
+    fn test_minisign_signature() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = Utf8PathBuf::from_path_buf(tempdir.path().join("test")).unwrap();
+        std::fs::write(format!("{}.minisig", path), MINISIGN_SIGNATURE).unwrap();
+
+        let trusted = TrustedKey::parse(MINISIGN_PUBLIC_KEY).expect("no error");
+        verify_signature(&path, b"test", std::slice::from_ref(&trusted)).expect("no error");
+        let err = verify_signature(&path, b"Test", &[trusted]).expect_err("tampered content");
+        assert!(err.downcast_ref::<BadSignatureError>().is_some());
+    }
+}
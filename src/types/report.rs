@@ -0,0 +1,185 @@
+use std::collections::VecDeque;
+
+use serde::Serialize;
+
+mod verification;
+
+pub use verification::{HashReport, ReportFormat, VerificationReport, WhitelistDecision};
+
+/// Result of a successful reproducible build in docker container
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BuildReport {
+    /// Path to the resulting wasm artifact on host
+    pub wasm_path: camino::Utf8PathBuf,
+    /// Generated name of build container, `<prefix>-<timestamp>-<pid>`, see [BuildOptions::container_name_prefix](crate::logic::nep330_build::BuildOptions::container_name_prefix)
+    pub container_name: String,
+    /// The exact `docker run` invocation, program followed by its arguments
+    pub docker_command: Vec<String>,
+    /// Output of build container, [Option::Some] if it was captured
+    pub log: Option<BuildLog>,
+    /// State of cargo cache, [Option::Some] if it was used
+    pub cargo_cache: Option<CargoCacheReport>,
+}
+
+/// Outcome of verification of a contract
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    /// Hash of built artifact matches expected one
+    Match,
+    Mismatch,
+    /// Validation, checkout or build failed
+    Failed,
+}
+
+/// Persistent cargo registry cache, which was mounted into build container
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CargoCacheReport {
+    /// sha256 hex of `build_environment` digest and `CARGO_HOME`
+    pub key: String,
+    /// Host directory of cache
+    pub path: camino::Utf8PathBuf,
+    /// Cache already contained downloaded crates before build
+    pub hit: bool,
+    /// Number of downloaded crates before build
+    pub crates_before: usize,
+    /// Number of downloaded crates after build, the difference is the number of crates,
+    /// which weren't found in cache
+    pub crates_after: usize,
+}
+
+/// Docker daemon, as reported by `docker info`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DaemonReport {
+    pub server_version: String,
+    /// Daemon runs in rootless mode
+    pub rootless: bool,
+    pub storage_driver: String,
+    /// Architecture of daemon's host, as reported by kernel, e.g. `x86_64` or `aarch64`
+    pub architecture: String,
+    pub os_type: String,
+}
+
+/// Local docker image, which was pulled or loaded for `build_environment` and verified against its digest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ImageReport {
+    /// `build_environment` reference, e.g. `sourcescan/cargo-near:0.13.4-rust-1.85.0@sha256:a9d8...`
+    pub reference: String,
+    /// `sha256:<hex>` digest, which `build_environment` is pinned to
+    pub digest: String,
+    /// Local image ID, `sha256:<hex>` of image config
+    pub id: String,
+    /// Platform of image, `<os>/<architecture>[/<variant>]`, e.g. `linux/amd64`
+    pub platform: String,
+    /// Local image archive, which the image was loaded from, [Option::None] if it was pulled from registry
+    pub archive: Option<camino::Utf8PathBuf>,
+}
+
+/// Platforms of docker daemon's host and build image
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlatformReport {
+    /// `<os>/<architecture>` of docker daemon's host, e.g. `linux/arm64`
+    pub host: String,
+    /// `<os>/<architecture>[/<variant>]` of image, e.g. `linux/amd64`
+    pub image: String,
+    /// Platform, which was explicitly requested with `--platform`
+    pub requested: Option<String>,
+    /// Build runs under emulation (e.g. QEMU), as image's architecture differs from host's one
+    ///
+    /// Emulated builds are known to produce artifacts with different hashes in some cases
+    pub emulated: bool,
+}
+
+/// Stream of build container's output, which a [LogLine] was read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LogLine {
+    pub stream: Stream,
+    /// Line without trailing newline, invalid utf8 sequences are replaced
+    pub line: String,
+}
+
+/// Captured output of build container
+///
+/// Only the last lines, which fit into the size limit, are kept
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct BuildLog {
+    pub lines: VecDeque<LogLine>,
+    /// Some of output was dropped to fit into the size limits
+    pub truncated: bool,
+    #[serde(skip)]
+    size: usize,
+}
+
+impl BuildLog {
+    /// Appends a line, dropping the oldest ones until total size of lines doesn't exceed `max_bytes`
+    ///
+    /// Only the end of a line, which doesn't fit into `max_bytes` on its own, is kept
+    pub fn push(&mut self, mut line: LogLine, max_bytes: usize) {
+        if line.line.len() > max_bytes {
+            let mut start = line.line.len() - max_bytes;
+            while !line.line.is_char_boundary(start) {
+                start += 1;
+            }
+            line.line.drain(..start);
+            self.truncated = true;
+        }
+        self.size += line.line.len();
+        self.lines.push_back(line);
+        while self.size > max_bytes {
+            let Some(dropped) = self.lines.pop_front() else {
+                break;
+            };
+            self.size -= dropped.line.len();
+            self.truncated = true;
+        }
+    }
+
+    /// Lines of both streams in order of arrival, joined with newlines
+    pub fn text(&self) -> String {
+        self.lines
+            .iter()
+            .map(|line| line.line.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BuildLog, LogLine, Stream};
+
+    fn line(s: &str) -> LogLine {
+        LogLine {
+            stream: Stream::Stdout,
+            line: s.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_build_log_size_limit() {
+        let mut log = BuildLog::default();
+        log.push(line("aaaa"), 10);
+        log.push(line("bbbb"), 10);
+        assert!(!log.truncated);
+        assert_eq!(log.text(), "aaaa\nbbbb");
+
+        log.push(line("cccc"), 10);
+        assert!(log.truncated);
+        assert_eq!(log.text(), "bbbb\ncccc");
+
+        log.push(line("a line longer than limit"), 10);
+        assert_eq!(log.text(), "than limit");
+
+        let mut log = BuildLog::default();
+        log.push(line("ünïcödé"), 4);
+        assert!(log.truncated);
+        assert_eq!(log.text(), "dé");
+    }
+}
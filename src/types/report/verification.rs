@@ -0,0 +1,544 @@
+use camino::Utf8Path;
+use serde::Serialize;
+
+use crate::logic::nep330_build::{BuildFailedError, CancelledError, TimeoutError};
+use crate::types::contract_source_metadata::{BuildInfo, ContractSourceMetadata};
+use crate::types::sha256_checksum::SHA256Checksum;
+use crate::types::whitelist::{Whitelist, WhitelistEntry};
+
+use super::{BuildLog, BuildReport, LogLine, Verdict};
+
+/// Number of last lines of build log, which are kept in [VerificationReport::log_excerpt]
+pub const LOG_EXCERPT_LINES: usize = 50;
+
+/// Outcome of checking `build_info` against a whitelist
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum WhitelistDecision {
+    /// No whitelist was configured, any image and command were allowed
+    NotConfigured,
+    /// Image and command prefix matched `entry`
+    Allowed {
+        entry: WhitelistEntry,
+    },
+    Rejected {
+        reason: String,
+    },
+}
+
+impl WhitelistDecision {
+    pub fn check(
+        contract_source_metadata: &ContractSourceMetadata,
+        whitelist: Option<&Whitelist>,
+    ) -> Self {
+        let Some(whitelist) = whitelist else {
+            return Self::NotConfigured;
+        };
+        let result = contract_source_metadata
+            .build_info
+            .as_ref()
+            .ok_or(eyre::eyre!(
+                "`build_info` field of `ContractSourceMetadata` cannot be null"
+            ))
+            .and_then(|build_info| {
+                let image = build_info.validate_build_env_on_regex()?;
+                let entry =
+                    BuildInfo::validate_build_image_on_whitelist(&image, whitelist.clone())?;
+                build_info.validate_build_command_on_whitelist(entry.clone())?;
+                Ok(entry)
+            });
+        match result {
+            Ok(entry) => Self::Allowed { entry },
+            Err(err) => Self::Rejected {
+                reason: format!("{:#}", err),
+            },
+        }
+    }
+}
+
+/// sha256 of a wasm artifact
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HashReport {
+    pub hex: String,
+    /// As displayed by explorers
+    pub base58: String,
+}
+
+impl From<&SHA256Checksum> for HashReport {
+    fn from(checksum: &SHA256Checksum) -> Self {
+        Self {
+            hex: checksum.to_hex_string(),
+            base58: checksum.to_base58_string(),
+        }
+    }
+}
+
+/// Summary of a single verification, which is rendered with [VerificationReport::render]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VerificationReport {
+    pub metadata: ContractSourceMetadata,
+    pub whitelist: WhitelistDecision,
+    /// The exact `docker run` invocation, [Option::Some] if the build has finished
+    pub docker_command: Option<Vec<String>>,
+    /// Last [LOG_EXCERPT_LINES] lines of build container's output, if it was captured
+    pub log_excerpt: Vec<LogLine>,
+    /// Earlier lines of build container's output were omitted
+    pub log_truncated: bool,
+    pub expected_hash: HashReport,
+    pub actual_hash: Option<HashReport>,
+    pub verdict: Verdict,
+    pub error: Option<String>,
+}
+
+/// Format of [VerificationReport::render]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// GitHub-flavored markdown, e.g. to be pasted into a pull request
+    Markdown,
+    /// Self-contained html page with inline styles
+    Html,
+    Text,
+}
+
+impl std::str::FromStr for ReportFormat {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "markdown" | "md" => Ok(Self::Markdown),
+            "html" => Ok(Self::Html),
+            "text" | "txt" => Ok(Self::Text),
+            other => Err(eyre::eyre!(
+                "unknown report format `{}`, expected one of `markdown`, `html`, `text`",
+                other
+            )),
+        }
+    }
+}
+
+impl ReportFormat {
+    /// Format by extension of `path`: `.md`, `.html`/`.htm`, or [ReportFormat::Text] otherwise
+    pub fn from_path(path: &Utf8Path) -> Self {
+        match path.extension() {
+            Some("md" | "markdown") => Self::Markdown,
+            Some("html" | "htm") => Self::Html,
+            _ => Self::Text,
+        }
+    }
+}
+
+impl VerificationReport {
+    /// `result` is the build report with hash of its artifact, or error of validation, checkout or build
+    pub fn new(
+        contract_source_metadata: ContractSourceMetadata,
+        whitelist: WhitelistDecision,
+        expected_hash: &SHA256Checksum,
+        result: Result<(&BuildReport, &SHA256Checksum), &eyre::Report>,
+    ) -> Self {
+        let (docker_command, log, actual_hash, verdict, error) = match result {
+            Ok((build, actual_hash)) => (
+                Some(build.docker_command.clone()),
+                build.log.as_ref(),
+                Some(HashReport::from(actual_hash)),
+                if actual_hash == expected_hash {
+                    Verdict::Match
+                } else {
+                    Verdict::Mismatch
+                },
+                None,
+            ),
+            Err(err) => (
+                None,
+                error_log(err),
+                None,
+                Verdict::Failed,
+                Some(format!("{:#}", err)),
+            ),
+        };
+        let (log_excerpt, log_truncated) = match log {
+            Some(log) => {
+                let skipped = log.lines.len().saturating_sub(LOG_EXCERPT_LINES);
+                (
+                    log.lines.iter().skip(skipped).cloned().collect(),
+                    log.truncated || skipped > 0,
+                )
+            }
+            None => (vec![], false),
+        };
+        Self {
+            metadata: contract_source_metadata,
+            whitelist,
+            docker_command,
+            log_excerpt,
+            log_truncated,
+            expected_hash: HashReport::from(expected_hash),
+            actual_hash,
+            verdict,
+            error,
+        }
+    }
+
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Markdown => self.render_markdown(),
+            ReportFormat::Html => self.render_html(),
+            ReportFormat::Text => self.render_text(),
+        }
+    }
+
+    fn title(&self) -> String {
+        let verdict = match self.verdict {
+            Verdict::Match => "MATCH",
+            Verdict::Mismatch => "MISMATCH",
+            Verdict::Failed => "FAILED",
+        };
+        format!("Verification report: {}", verdict)
+    }
+
+    /// Content of report, shared by all formats
+    fn sections(&self) -> Vec<Section> {
+        let mut sections = vec![];
+
+        let mut metadata = vec![];
+        if let Some(ref version) = self.metadata.version {
+            metadata.push(("version", version.clone()));
+        }
+        if let Some(ref link) = self.metadata.link {
+            metadata.push(("link", link.clone()));
+        }
+        if let Some(ref build_info) = self.metadata.build_info {
+            metadata.push((
+                "source_code_snapshot",
+                build_info.source_code_snapshot.clone(),
+            ));
+            metadata.push(("build_environment", build_info.build_environment.clone()));
+            metadata.push((
+                "build_command",
+                shell_words::join(&build_info.build_command),
+            ));
+            metadata.push(("contract_path", format!("{:?}", build_info.contract_path)));
+        }
+        sections.push(Section {
+            title: "Metadata",
+            blocks: vec![Block::Fields(metadata)],
+        });
+
+        let whitelist = match self.whitelist {
+            WhitelistDecision::NotConfigured => {
+                vec![Block::Text("No whitelist configured.".to_string())]
+            }
+            WhitelistDecision::Allowed { ref entry } => vec![Block::Fields(vec![
+                ("decision", "allowed".to_string()),
+                ("image", entry.expected_docker_image.clone()),
+                (
+                    "command prefix",
+                    shell_words::join(&entry.expected_command_prefix),
+                ),
+            ])],
+            WhitelistDecision::Rejected { ref reason } => vec![Block::Fields(vec![
+                ("decision", "rejected".to_string()),
+                ("reason", reason.clone()),
+            ])],
+        };
+        sections.push(Section {
+            title: "Whitelist",
+            blocks: whitelist,
+        });
+
+        if let Some(ref docker_command) = self.docker_command {
+            sections.push(Section {
+                title: "Docker invocation",
+                blocks: vec![Block::Code(shell_words::join(docker_command))],
+            });
+        }
+
+        if !self.log_excerpt.is_empty() {
+            let mut blocks = vec![];
+            if self.log_truncated {
+                blocks.push(Block::Text(format!(
+                    "Last {} lines of output.",
+                    self.log_excerpt.len()
+                )));
+            }
+            blocks.push(Block::Code(
+                self.log_excerpt
+                    .iter()
+                    .map(|line| line.line.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ));
+            sections.push(Section {
+                title: "Build log",
+                blocks,
+            });
+        }
+
+        let mut hashes = vec![
+            ("expected (base58)", self.expected_hash.base58.clone()),
+            ("expected (hex)", self.expected_hash.hex.clone()),
+        ];
+        if let Some(ref actual_hash) = self.actual_hash {
+            hashes.push(("actual (base58)", actual_hash.base58.clone()));
+            hashes.push(("actual (hex)", actual_hash.hex.clone()));
+        }
+        sections.push(Section {
+            title: "Hashes",
+            blocks: vec![Block::Fields(hashes)],
+        });
+
+        if let Some(ref error) = self.error {
+            sections.push(Section {
+                title: "Error",
+                blocks: vec![Block::Code(error.clone())],
+            });
+        }
+        sections
+    }
+
+    fn render_text(&self) -> String {
+        let mut out = format!("{}\n", self.title());
+        for section in self.sections() {
+            out.push_str(&format!(
+                "\n{}\n{}\n",
+                section.title,
+                "-".repeat(section.title.len())
+            ));
+            for block in section.blocks {
+                match block {
+                    Block::Fields(fields) => {
+                        for (key, value) in fields {
+                            out.push_str(&format!("{}: {}\n", key, value));
+                        }
+                    }
+                    Block::Code(code) => {
+                        out.push_str(&crate::pretty_print::indent_payload(&code));
+                        out.push('\n');
+                    }
+                    Block::Text(text) => out.push_str(&format!("{}\n", text)),
+                }
+            }
+        }
+        out
+    }
+
+    fn render_markdown(&self) -> String {
+        let mut out = format!("# {}\n", self.title());
+        for section in self.sections() {
+            out.push_str(&format!("\n## {}\n\n", section.title));
+            for block in section.blocks {
+                match block {
+                    Block::Fields(fields) => {
+                        for (key, value) in fields {
+                            out.push_str(&format!(
+                                "- **{}**: {}\n",
+                                key,
+                                markdown_code_span(&value)
+                            ));
+                        }
+                    }
+                    Block::Code(code) => {
+                        let mut fence = "```".to_string();
+                        while code.contains(&fence) {
+                            fence.push('`');
+                        }
+                        out.push_str(&format!("{}\n{}\n{}\n", fence, code, fence));
+                    }
+                    Block::Text(text) => out.push_str(&format!("{}\n", text)),
+                }
+            }
+        }
+        out
+    }
+
+    fn render_html(&self) -> String {
+        let title = html_escape(&self.title());
+        let mut out = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+            <style>{}</style>\n</head>\n<body>\n<h1 class=\"{}\">{}</h1>\n",
+            title,
+            HTML_STYLE,
+            serde_json::to_value(self.verdict)
+                .ok()
+                .and_then(|verdict| verdict.as_str().map(str::to_string))
+                .unwrap_or_default(),
+            title
+        );
+        for section in self.sections() {
+            out.push_str(&format!("<h2>{}</h2>\n", html_escape(section.title)));
+            for block in section.blocks {
+                match block {
+                    Block::Fields(fields) => {
+                        out.push_str("<table>\n");
+                        for (key, value) in fields {
+                            out.push_str(&format!(
+                                "<tr><th>{}</th><td><code>{}</code></td></tr>\n",
+                                html_escape(key),
+                                html_escape(&value)
+                            ));
+                        }
+                        out.push_str("</table>\n");
+                    }
+                    Block::Code(code) => {
+                        out.push_str(&format!("<pre>{}</pre>\n", html_escape(&code)))
+                    }
+                    Block::Text(text) => out.push_str(&format!("<p>{}</p>\n", html_escape(&text))),
+                }
+            }
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+}
+
+const HTML_STYLE: &str =
+    "body{font-family:sans-serif;max-width:960px;margin:2em auto;padding:0 1em}\
+    pre{background:#f4f4f4;padding:1em;overflow-x:auto}\
+    th{text-align:left;padding-right:1em;vertical-align:top}\
+    .match{color:#1a7f37}.mismatch,.failed{color:#cf222e}";
+
+struct Section {
+    title: &'static str,
+    blocks: Vec<Block>,
+}
+
+enum Block {
+    Fields(Vec<(&'static str, String)>),
+    Code(String),
+    Text(String),
+}
+
+/// Captured output of build container, which is attached to build errors
+fn error_log(err: &eyre::Report) -> Option<&BuildLog> {
+    if let Some(err) = err.downcast_ref::<BuildFailedError>() {
+        return err.log.as_ref();
+    }
+    if let Some(err) = err.downcast_ref::<TimeoutError>() {
+        return err.log.as_ref();
+    }
+    err.downcast_ref::<CancelledError>()
+        .and_then(|err| err.log.as_ref())
+}
+
+/// Inline code span, which delimiter is longer than any run of backticks in `s`
+fn markdown_code_span(s: &str) -> String {
+    let mut fence = "`".to_string();
+    while s.contains(&fence) {
+        fence.push('`');
+    }
+    // a space is stripped from both sides of a span, which starts or ends with a backtick
+    let padding = if s.starts_with('`') || s.ends_with('`') {
+        " "
+    } else {
+        ""
+    };
+    format!("{}{}{}{}{}", fence, padding, s, padding, fence)
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::logic::nep330_build::BuildFailedError;
+    use crate::types::contract_source_metadata::ContractSourceMetadata;
+    use crate::types::report::{BuildLog, LogLine, Stream, Verdict};
+    use crate::types::sha256_checksum::SHA256Checksum;
+    use crate::types::whitelist::WhitelistEntry;
+
+    use super::{ReportFormat, VerificationReport, WhitelistDecision};
+
+    fn metadata() -> ContractSourceMetadata {
+        serde_json::from_str(
+            r#"{
+  "build_info": {
+    "build_command": ["cargo", "near", "build", "non-reproducible-wasm", "--locked"],
+    "build_environment": "sourcescan/cargo-near:0.13.4-rust-1.85.0@sha256:a9d8bee7b134856cc8baa142494a177f2ba9ecfededfcdd38f634e14cca8aae2",
+    "contract_path": "",
+    "source_code_snapshot": "git+https://github.com/dj8yfo/verify_contracts_collection?rev=e3303f0cf8761b99a84d6b4a2f5e2e7f5bbe1e9a"
+  },
+  "link": null,
+  "standards": [],
+  "version": "0.1.0"
+}"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_whitelist_decision() {
+        let entry = WhitelistEntry {
+            expected_docker_image: "sourcescan/cargo-near".into(),
+            expected_command_prefix: vec!["cargo".into(), "near".into(), "build".into()],
+        };
+        assert_eq!(
+            WhitelistDecision::check(&metadata(), Some(&vec![entry.clone()])),
+            WhitelistDecision::Allowed { entry }
+        );
+        assert!(matches!(
+            WhitelistDecision::check(&metadata(), Some(&vec![])),
+            WhitelistDecision::Rejected { .. }
+        ));
+    }
+
+    #[test]
+    fn test_render_failed_build() {
+        let mut log = BuildLog::default();
+        for i in 0..60 {
+            log.push(
+                LogLine {
+                    stream: Stream::Stderr,
+                    line: format!("error <{}>", i),
+                },
+                usize::MAX,
+            );
+        }
+        let err = eyre::Report::new(BuildFailedError {
+            exit_status: "exit status: 101".into(),
+            exit_code: Some(101),
+            log: Some(log),
+        });
+        let report = VerificationReport::new(
+            metadata(),
+            WhitelistDecision::NotConfigured,
+            &SHA256Checksum { hash: vec![1; 32] },
+            Err(&err),
+        );
+        assert_eq!(report.verdict, Verdict::Failed);
+        assert_eq!(report.log_excerpt.len(), super::LOG_EXCERPT_LINES);
+        assert!(report.log_truncated);
+
+        let text = report.render(ReportFormat::Text);
+        assert!(text.starts_with("Verification report: FAILED"));
+        assert!(text.contains("error <59>"));
+        assert!(!text.contains("error <9>"));
+        let markdown = report.render(ReportFormat::Markdown);
+        assert!(markdown.contains("## Build log\n\nLast 50 lines of output.\n```\n"));
+        let html = report.render(ReportFormat::Html);
+        assert!(html.contains("error &lt;59&gt;"));
+        assert!(html.contains("<h1 class=\"failed\">"));
+    }
+
+    #[test]
+    fn test_render_markdown_rejected() {
+        let decision = WhitelistDecision::Rejected {
+            reason: "`build_info` field of `ContractSourceMetadata` cannot be null".into(),
+        };
+        let err = eyre::eyre!("whitelist check failed");
+        let report = VerificationReport::new(
+            metadata(),
+            decision,
+            &SHA256Checksum { hash: vec![1; 32] },
+            Err(&err),
+        );
+        let markdown = report.render(ReportFormat::Markdown);
+        assert!(markdown.contains("- **decision**: `rejected`\n"));
+        assert!(markdown.contains(
+            "- **reason**: `` `build_info` field of `ContractSourceMetadata` cannot be null ``\n"
+        ));
+        assert_eq!(super::markdown_code_span("a``b"), "```a``b```");
+    }
+}
@@ -1,3 +1,4 @@
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SHA256Checksum {
     pub hash: Vec<u8>,
 }
@@ -10,4 +11,36 @@ impl SHA256Checksum {
     pub fn to_base58_string(&self) -> String {
         bs58::encode(&self.hash).into_string()
     }
+
+    /// Parses either a 64-character hex string or a base58 string (as displayed by explorers)
+    pub fn parse(s: &str) -> eyre::Result<Self> {
+        let hash = if s.len() == 64 && s.chars().all(|c| c.is_ascii_hexdigit()) {
+            hex::decode(s)?
+        } else {
+            bs58::decode(s)
+                .into_vec()
+                .map_err(|err| eyre::eyre!("`{}` is neither hex nor base58: {}", s, err))?
+        };
+        if hash.len() != 32 {
+            return Err(eyre::eyre!(
+                "`{}` is {} bytes long, sha256 is 32 bytes",
+                s,
+                hash.len()
+            ));
+        }
+        Ok(Self { hash })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SHA256Checksum;
+
+    #[test]
+    fn test_parse_hex_and_base58() {
+        let base58 = SHA256Checksum::parse("5KaX9FM9NtjpfahksL8TMWQk3LF7k8Sv88Qem4tGrVDW").unwrap();
+        let hex = SHA256Checksum::parse(&base58.to_hex_string()).unwrap();
+        assert_eq!(base58, hex);
+        assert!(SHA256Checksum::parse("abc").is_err());
+    }
 }
@@ -7,3 +7,242 @@ pub struct WhitelistEntry {
     pub expected_docker_image: String,
     pub expected_command_prefix: Vec<String>,
 }
+
+/// Repository name of docker image reference without tag and digest, optionally with registry domain,
+/// as per [distribution reference grammar](https://github.com/distribution/reference/blob/main/reference.go)
+pub const DOCKER_IMAGE_NAME_REGEX_PATTERN: &str = r"^(?:[a-zA-Z0-9](?:[a-zA-Z0-9-]*[a-zA-Z0-9])?(?:\.[a-zA-Z0-9](?:[a-zA-Z0-9-]*[a-zA-Z0-9])?)*(?::[0-9]+)?/)?[a-z0-9]+(?:(?:[._]|__|-+)[a-z0-9]+)*(?:/[a-z0-9]+(?:(?:[._]|__|-+)[a-z0-9]+)*)*$";
+
+const DOCKER_HUB_DOMAINS: [&str; 3] = ["docker.io", "index.docker.io", "registry-1.docker.io"];
+
+/// A problem with one or more entries of whitelist, found by [lint]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LintIssue {
+    /// Indexes of entries in whitelist
+    pub entries: Vec<usize>,
+    pub message: String,
+}
+
+impl std::fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "entries {:?}: {}", self.entries, self.message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct LintReport {
+    /// Issues, which make whitelist unusable
+    pub errors: Vec<LintIssue>,
+    /// Issues, which are likely mistakes
+    pub warnings: Vec<LintIssue>,
+}
+
+impl LintReport {
+    /// Error listing all of [LintReport::errors], if any, warnings otherwise
+    pub fn into_result(self) -> eyre::Result<Vec<LintIssue>> {
+        if self.errors.is_empty() {
+            return Ok(self.warnings);
+        }
+        let errors = self
+            .errors
+            .iter()
+            .map(|issue| format!("  {}", issue))
+            .collect::<Vec<_>>()
+            .join("\n");
+        Err(eyre::eyre!("invalid whitelist:\n{}", errors))
+    }
+}
+
+/// Entries sorted by `(expected_docker_image, expected_command_prefix)`,
+/// so that the same entries in any order are hashed the same way
+pub(crate) fn sorted(whitelist: &[WhitelistEntry]) -> Whitelist {
+    let mut whitelist = whitelist.to_vec();
+    whitelist.sort_by(|a, b| {
+        (&a.expected_docker_image, &a.expected_command_prefix)
+            .cmp(&(&b.expected_docker_image, &b.expected_command_prefix))
+    });
+    whitelist
+}
+
+/// Checks whitelist for entries, which can't match anything or would make validation panic
+///
+/// Errors: empty `expected_command_prefix` or tokens in it, duplicate `expected_docker_image`,
+/// image names which aren't valid repository names (e.g. with a tag or digest, which are never matched).
+///
+/// Warnings: different spellings of the same image (e.g. `docker.io/sourcescan/cargo-near`
+/// and `sourcescan/cargo-near`), as metadata is only matched against exact spelling,
+/// and command prefixes of such entries shadowing each other.
+pub fn lint(whitelist: &[WhitelistEntry]) -> LintReport {
+    let regex = regex::Regex::new(DOCKER_IMAGE_NAME_REGEX_PATTERN).expect("no error");
+    let mut report = LintReport::default();
+
+    for (index, entry) in whitelist.iter().enumerate() {
+        if entry.expected_command_prefix.is_empty() {
+            report.errors.push(LintIssue {
+                entries: vec![index],
+                message: format!(
+                    "empty `expected_command_prefix` of `{}`",
+                    entry.expected_docker_image
+                ),
+            });
+        } else if entry.expected_command_prefix.iter().any(String::is_empty) {
+            report.errors.push(LintIssue {
+                entries: vec![index],
+                message: format!(
+                    "empty token in `expected_command_prefix` {:?}",
+                    entry.expected_command_prefix
+                ),
+            });
+        }
+        if !regex.is_match(&entry.expected_docker_image) {
+            report.errors.push(LintIssue {
+                entries: vec![index],
+                message: format!(
+                    "`{}` isn't a valid docker image name without tag and digest",
+                    entry.expected_docker_image
+                ),
+            });
+        }
+    }
+
+    for (index, entry) in whitelist.iter().enumerate() {
+        for (other_index, other) in whitelist.iter().enumerate().skip(index + 1) {
+            if entry.expected_docker_image == other.expected_docker_image {
+                report.errors.push(LintIssue {
+                    entries: vec![index, other_index],
+                    message: format!(
+                        "duplicate `expected_docker_image` `{}`, only the first entry is ever used",
+                        entry.expected_docker_image
+                    ),
+                });
+                continue;
+            }
+            if normalize_image_name(&entry.expected_docker_image)
+                != normalize_image_name(&other.expected_docker_image)
+            {
+                continue;
+            }
+            let message = if other
+                .expected_command_prefix
+                .starts_with(&entry.expected_command_prefix)
+                || entry
+                    .expected_command_prefix
+                    .starts_with(&other.expected_command_prefix)
+            {
+                format!(
+                    "`{}` and `{}` are the same image, and command prefix {:?} shadows {:?}",
+                    entry.expected_docker_image,
+                    other.expected_docker_image,
+                    shorter(
+                        &entry.expected_command_prefix,
+                        &other.expected_command_prefix
+                    ),
+                    longer(
+                        &entry.expected_command_prefix,
+                        &other.expected_command_prefix
+                    ),
+                )
+            } else {
+                format!(
+                    "`{}` and `{}` are the same image with different command prefixes, \
+                    which one applies depends on spelling of image in metadata",
+                    entry.expected_docker_image, other.expected_docker_image,
+                )
+            };
+            report.warnings.push(LintIssue {
+                entries: vec![index, other_index],
+                message,
+            });
+        }
+    }
+    report
+}
+
+/// Image name with docker hub domain and `library/` namespace stripped
+fn normalize_image_name(image: &str) -> &str {
+    let image = match image.split_once('/') {
+        Some((domain, rest)) if DOCKER_HUB_DOMAINS.contains(&domain) => rest,
+        _ => image,
+    };
+    match image.strip_prefix("library/") {
+        Some(rest) if !rest.contains('/') => rest,
+        _ => image,
+    }
+}
+
+fn shorter<'a>(a: &'a [String], b: &'a [String]) -> &'a [String] {
+    if a.len() <= b.len() {
+        a
+    } else {
+        b
+    }
+}
+
+fn longer<'a>(a: &'a [String], b: &'a [String]) -> &'a [String] {
+    if a.len() > b.len() {
+        a
+    } else {
+        b
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lint, WhitelistEntry};
+
+    fn entry(image: &str, prefix: &[&str]) -> WhitelistEntry {
+        WhitelistEntry {
+            expected_docker_image: image.into(),
+            expected_command_prefix: prefix.iter().map(|token| token.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_lint_ok_resources() {
+        for file in [
+            "tests/resources/whitelist_ok_nonstandard_image.json",
+            "tests/resources/whitelist_err_image.json",
+            "tests/resources/whitelist_err_command.json",
+        ] {
+            let whitelist: super::Whitelist =
+                serde_json::from_slice(&std::fs::read(file).unwrap()).unwrap();
+            assert_eq!(lint(&whitelist), Default::default(), "{}", file);
+        }
+    }
+
+    #[test]
+    fn test_lint_errors() {
+        let report = lint(&[
+            entry("sourcescan/cargo-near", &[]),
+            entry("sourcescan/cargo-near", &["cargo", "near", "build"]),
+            entry("sourcescan/cargo-near:0.13.4", &["cargo", "near"]),
+            entry("sourcescan/Cargo-near", &["cargo", ""]),
+        ]);
+        let errors = report
+            .errors
+            .iter()
+            .map(|issue| issue.entries.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(errors, vec![vec![0], vec![2], vec![3], vec![3], vec![0, 1]]);
+        assert!(report.into_result().is_err());
+    }
+
+    #[test]
+    fn test_lint_overlap_warnings() {
+        let report = lint(&[
+            entry("sourcescan/cargo-near", &["cargo", "near", "build"]),
+            entry("docker.io/sourcescan/cargo-near", &["cargo", "near"]),
+            entry("rust", &["cargo", "build"]),
+            entry("docker.io/library/rust", &["cargo", "near"]),
+            entry("localhost:5000/sourcescan/cargo-near", &["cargo"]),
+        ]);
+        assert!(report.errors.is_empty());
+        let warnings = report
+            .warnings
+            .iter()
+            .map(|issue| issue.entries.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(warnings, vec![vec![0, 1], vec![2, 3]]);
+        assert!(report.warnings[0].message.contains("shadows"));
+        assert!(report.warnings[1].message.contains("depends on spelling"));
+    }
+}